@@ -1,4 +1,8 @@
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(evil_json_nightly)");
+    println!("cargo:rustc-check-cfg=cfg(evil_json_1_46)");
+    println!("cargo:rustc-check-cfg=cfg(evil_json_1_89)");
+
     if version_check::is_feature_flaggable() == Some(true) {
         println!("cargo:rustc-cfg=evil_json_nightly");
     }
@@ -6,4 +10,9 @@ fn main() {
     if version_check::is_min_version("1.46.0") == Some(true) {
         println!("cargo:rustc-cfg=evil_json_1_46");
     }
+
+    // AVX-512 intrinsics are stabilized in Rust 1.89.0
+    if version_check::is_min_version("1.89.0") == Some(true) {
+        println!("cargo:rustc-cfg=evil_json_1_89");
+    }
 }
@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
-use evil_json_bench::{CitmCatalog, Twitter};
+use evil_json_bench::{Canada, CitmCatalog, Twitter};
 use simd_json_derive::Serialize;
 use std::io;
 use std::path::Path;
@@ -11,6 +11,37 @@ fn get_json(name: &str) -> io::Result<String> {
     std::fs::read_to_string(path)
 }
 
+fn canada(c: &mut Criterion) {
+    let canada_str = get_json("canada").expect("Failed to read canada.json");
+
+    let mut group = c.benchmark_group("canada.json");
+    group.throughput(Throughput::Bytes(canada_str.len() as u64));
+
+    let canada: Canada = serde_json::from_str(canada_str.as_str()).unwrap();
+
+    group.throughput(Throughput::Bytes(
+        serde_json::to_string(&canada).unwrap().len() as u64,
+    ));
+    group.bench_function("serde-json", |b| b.iter(|| serde_json::to_string(&canada)));
+
+    group.throughput(Throughput::Bytes(
+        simd_json::to_string(&canada).unwrap().len() as u64,
+    ));
+    group.bench_function("simd-json", |b| b.iter(|| simd_json::to_string(&canada)));
+
+    group.throughput(Throughput::Bytes(
+        canada.json_string().unwrap().len() as u64
+    ));
+    group.bench_function("simd-json-derive", |b| b.iter(|| canada.json_string()));
+
+    group.throughput(Throughput::Bytes(
+        evil_json::to_string(&canada).unwrap().len() as u64,
+    ));
+    group.bench_function("evil-json", |b| b.iter(|| evil_json::to_string(&canada)));
+
+    group.finish();
+}
+
 fn citm_catalog(c: &mut Criterion) {
     let citm_catalog_str =
         get_json("citm_catalog").expect("Failed to read citm_catalog.json");
@@ -83,5 +114,5 @@ fn twitter(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, citm_catalog, twitter);
+criterion_group!(benches, canada, citm_catalog, twitter);
 criterion_main!(benches);
@@ -0,0 +1,59 @@
+use std::io;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[derive(simd_json_derive::Serialize)]
+pub struct Canada {
+    #[serde(rename = "type")]
+    pub obj_type: ObjType,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[derive(simd_json_derive::Serialize)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    pub obj_type: ObjType,
+    pub properties: Properties,
+    pub geometry: Geometry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[derive(simd_json_derive::Serialize)]
+pub struct Properties {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[derive(simd_json_derive::Serialize)]
+pub struct Geometry {
+    #[serde(rename = "type")]
+    pub obj_type: ObjType,
+    pub coordinates: Vec<Vec<Point>>,
+}
+
+/// Longitude and latitude
+#[derive(Serialize, Deserialize)]
+pub struct Point(pub f64, pub f64);
+
+impl simd_json_derive::Serialize for Point {
+    fn json_write<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_all(b"[")?;
+        simd_json_derive::Serialize::json_write(&self.0, writer)?;
+        writer.write_all(b",")?;
+        simd_json_derive::Serialize::json_write(&self.1, writer)?;
+        writer.write_all(b"]")
+    }
+}
+
+enum_str!(ObjType {
+    FeatureCollection("FeatureCollection"),
+    Feature("Feature"),
+    Polygon("Polygon"),
+});
@@ -3,8 +3,10 @@ extern crate serde_derive;
 
 #[macro_use]
 mod common;
+mod canada;
 mod citm_catalog;
 mod twitter;
 
+pub use canada::*;
 pub use citm_catalog::*;
 pub use twitter::*;
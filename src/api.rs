@@ -1,6 +1,11 @@
+use crate::bufwrite::BufWrite;
 use crate::bufwriter::BufWriter;
+use crate::countwriter::CountWriter;
 use crate::error::Error;
+use crate::options::Options;
 use crate::ser::compact::Serializer;
+use crate::ser::PrettyFormatter;
+use crate::slicewriter::SliceWriter;
 
 use serde::ser::Serialize;
 use std::io;
@@ -15,6 +20,47 @@ where
     Ok(vec)
 }
 
+pub fn to_vec_with<T>(value: &T, options: &Options) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut vec = Vec::new();
+    let mut ser = Serializer::with_options(&mut vec, options);
+    tri!(value.serialize(&mut ser));
+    Ok(vec)
+}
+
+/// Truncates `vec` to `len` when dropped, unless `len` is moved past the
+/// appended output
+struct Guard<'a> {
+    vec: &'a mut Vec<u8>,
+    len: usize,
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        // restore the original contents if serialization failed or panicked
+        self.vec.truncate(self.len);
+    }
+}
+
+/// Serializes `value` and appends the output to `vec`
+///
+/// On error, or if `value` panics, `vec` is truncated to its original
+/// length.
+pub fn to_vec_into<T>(vec: &mut Vec<u8>, value: &T) -> Result<(), Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut guard = Guard {
+        len: vec.len(),
+        vec,
+    };
+    tri!(value.serialize(&mut Serializer::new(&mut *guard.vec)));
+    guard.len = guard.vec.len();
+    Ok(())
+}
+
 pub fn to_string<T>(value: &T) -> Result<String, Error>
 where
     T: Serialize + ?Sized,
@@ -24,6 +70,71 @@ where
     unsafe { Ok(String::from_utf8_unchecked(vec)) }
 }
 
+pub fn to_string_with<T>(value: &T, options: &Options) -> Result<String, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let vec = tri!(to_vec_with(value, options));
+    // SAFETY: JSON data contains only valid UTF-8 sequence
+    unsafe { Ok(String::from_utf8_unchecked(vec)) }
+}
+
+/// Serializes `value` and appends the output to `string`
+///
+/// On error, or if `value` panics, `string` is truncated to its original
+/// length.
+pub fn to_string_into<T>(string: &mut String, value: &T) -> Result<(), Error>
+where
+    T: Serialize + ?Sized,
+{
+    // SAFETY: JSON data contains only valid UTF-8 sequence, and `guard`
+    // removes incomplete output before the string is used again
+    let vec = unsafe { string.as_mut_vec() };
+    let mut guard = Guard {
+        len: vec.len(),
+        vec,
+    };
+    tri!(value.serialize(&mut Serializer::new(&mut *guard.vec)));
+    guard.len = guard.vec.len();
+    Ok(())
+}
+
+/// Serializes `value` into `buf` and returns the number of bytes written
+///
+/// Returns [`Error::BufferTooSmall`] if the output does not fit in `buf`.
+/// The contents of `buf` are unspecified on error.
+pub fn to_slice<T>(buf: &mut [u8], value: &T) -> Result<usize, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut writer = SliceWriter::new(buf);
+    tri!(value.serialize(&mut Serializer::new(&mut writer)));
+    writer.finish().map_err(|required| Error::BufferTooSmall {
+        required: Some(required),
+    })
+}
+
+/// Serializes `value` into a custom [`BufWrite`] implementation
+pub fn to_buf_write<B, T>(writer: &mut B, value: &T) -> Result<(), Error>
+where
+    B: BufWrite,
+    T: Serialize + ?Sized,
+{
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Returns the length of the JSON output of `value` without writing it
+///
+/// The result is exactly the length of the output of [`to_vec`].
+pub fn serialized_len<T>(value: &T) -> Result<usize, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut writer = CountWriter::new();
+    tri!(value.serialize(&mut Serializer::new(&mut writer)));
+    Ok(writer.count())
+}
+
 pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), Error>
 where
     W: io::Write,
@@ -32,5 +143,63 @@ where
     // We use our self-implemented bufwriter, which is faster than std crate.
     let mut bufwriter = BufWriter::new(writer);
     let mut ser = Serializer::new(&mut bufwriter);
-    value.serialize(&mut ser)
+    tri!(value.serialize(&mut ser));
+    imap!(bufwriter.flush_buf())
+}
+
+/// Same as [`to_writer`], but also flushes `writer` itself after the data is
+/// written
+///
+/// This is useful for writers which buffer data internally, such as
+/// `std::io::BufWriter<File>` or compression streams.
+pub fn to_writer_flushed<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    let mut bufwriter = BufWriter::new(writer);
+    let mut ser = Serializer::new(&mut bufwriter);
+    tri!(value.serialize(&mut ser));
+    imap!(io::Write::flush(&mut bufwriter))
+}
+
+pub fn to_writer_with<W, T>(writer: W, value: &T, options: &Options) -> Result<(), Error>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    let mut bufwriter = BufWriter::new(writer);
+    let mut ser = Serializer::with_options(&mut bufwriter, options);
+    tri!(value.serialize(&mut ser));
+    imap!(bufwriter.flush_buf())
+}
+
+pub fn to_vec_pretty<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut vec = Vec::new();
+    let mut ser = Serializer::with_formatter(&mut vec, PrettyFormatter::new());
+    tri!(value.serialize(&mut ser));
+    Ok(vec)
+}
+
+pub fn to_string_pretty<T>(value: &T) -> Result<String, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let vec = tri!(to_vec_pretty(value));
+    // SAFETY: JSON data contains only valid UTF-8 sequence
+    unsafe { Ok(String::from_utf8_unchecked(vec)) }
+}
+
+pub fn to_writer_pretty<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: io::Write,
+    T: Serialize,
+{
+    let mut bufwriter = BufWriter::new(writer);
+    let mut ser = Serializer::with_formatter(&mut bufwriter, PrettyFormatter::new());
+    tri!(value.serialize(&mut ser));
+    imap!(bufwriter.flush_buf())
 }
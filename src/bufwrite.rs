@@ -1,18 +1,124 @@
 use crate::raw::SerializeRaw;
 
+use core::cmp;
+use core::ptr;
 use std::io;
 
-/// A `BufWrite` is a type of `Write`r which has an internal buffer
+/// A `Write`r which lets serializers format values directly into its buffer
 ///
-/// This trait is unsafe because incorrect implementation of `next_ptr` or
-/// `reserve` method causes UB.
-#[doc(hidden)]
+/// Serializers first call [`reserve`] to make room for a value, then write
+/// the bytes at [`next_ptr`] and commit them with [`advance`]. Strings and
+/// other variable-length data are written with `io::Write::write_all`.
+///
+/// Implement this trait to serialize into custom buffers (e.g. arenas or ring
+/// buffers) through [`to_buf_write`](crate::to_buf_write).
+///
+/// # Safety
+///
+/// Implementors must uphold the following contracts, since serializers write
+/// through raw pointers without bounds checks.
+///
+/// * After `reserve(n)` returns `Ok(())`, `next_ptr()` must return a pointer
+///   which is valid for writes of `n` bytes. It stays valid until the next
+///   call to any method other than `next_ptr` and `advance`.
+/// * `reserve` must not fail because `n` is too large for an internal buffer.
+///   Only I/O errors may be returned.
+/// * Output written with `write_all` or committed with `advance` is the
+///   output of the writer, in order. Serializers never take it back, so it
+///   may be emitted right away.
+///
+/// # Example
+///
+/// ```
+/// use evil_json::BufWrite;
+/// use std::io;
+///
+/// /// Output buffer which is shared between messages
+/// struct Message<'a> {
+///     buf: &'a mut Vec<u8>,
+/// }
+///
+/// impl<'a> io::Write for Message<'a> {
+///     fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+///         self.buf.extend_from_slice(data);
+///         Ok(data.len())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// unsafe impl<'a> BufWrite for Message<'a> {
+///     fn next_ptr(&mut self) -> *mut u8 {
+///         self.buf.next_ptr()
+///     }
+///
+///     fn reserve(&mut self, additional: usize) -> io::Result<()> {
+///         BufWrite::reserve(self.buf, additional)
+///     }
+///
+///     unsafe fn advance(&mut self, additional: usize) {
+///         self.buf.advance(additional);
+///     }
+///
+///     unsafe fn shrink(&mut self, shrink_size: usize) {
+///         self.buf.shrink(shrink_size);
+///     }
+/// }
+///
+/// let mut buf = Vec::new();
+/// evil_json::to_buf_write(&mut Message { buf: &mut buf }, &[1, 2, 3]).unwrap();
+/// evil_json::to_buf_write(&mut Message { buf: &mut buf }, "abc").unwrap();
+/// assert_eq!(buf, br#"[1,2,3]"abc""#);
+/// ```
+///
+/// [`reserve`]: BufWrite::reserve
+/// [`next_ptr`]: BufWrite::next_ptr
+/// [`advance`]: BufWrite::advance
 pub unsafe trait BufWrite: io::Write {
+    /// Returns a pointer to the end of the output
     fn next_ptr(&mut self) -> *mut u8;
+
+    /// Makes room for at least `additional` bytes at `next_ptr()`
     fn reserve(&mut self, additional: usize) -> Result<(), io::Error>;
+
+    /// Appends `additional` bytes written at `next_ptr()` to the output
+    ///
+    /// # Safety
+    ///
+    /// `additional` bytes must have been reserved with `reserve` and
+    /// initialized.
     unsafe fn advance(&mut self, additional: usize);
+
+    /// Removes the last `shrink_size` bytes from the output
+    ///
+    /// Serializers only call this on the writers of this crate.
+    ///
+    /// # Safety
+    ///
+    /// `shrink_size` must not be greater than 2, nor than the number of bytes
+    /// written since the writer was created.
     unsafe fn shrink(&mut self, shrink_size: usize);
 
+    /// Returns the size of the pieces in which long strings and bytes are
+    /// written, instead of reserving their worst-case length at once
+    #[doc(hidden)]
+    #[inline]
+    fn max_reserve(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Set by the writers of this crate, which keep the last `MAX_SUFFIX_LEN`
+    /// bytes of the output in memory until more bytes are written
+    ///
+    /// Serializers write a separator together with the value before it only
+    /// into these writers, and remove or rewrite it through `shrink` and
+    /// `next_ptr().sub(..)` when the container is closed.
+    #[doc(hidden)]
+    const KEEPS_TAIL: bool = false;
+
+    #[doc(hidden)]
     #[inline]
     fn write1<T1: SerializeRaw>(&mut self, value: &T1) -> Result<(), io::Error> {
         let hint = value.size_hint();
@@ -22,6 +128,7 @@ pub unsafe trait BufWrite: io::Write {
         })
     }
 
+    #[doc(hidden)]
     #[inline]
     fn write2<T1: SerializeRaw, T2: SerializeRaw>(
         &mut self,
@@ -37,6 +144,7 @@ pub unsafe trait BufWrite: io::Write {
         })
     }
 
+    #[doc(hidden)]
     #[inline]
     fn write3<T1: SerializeRaw, T2: SerializeRaw, T3: SerializeRaw>(
         &mut self,
@@ -57,6 +165,7 @@ pub unsafe trait BufWrite: io::Write {
         })
     }
 
+    #[doc(hidden)]
     #[inline]
     fn write4<T1: SerializeRaw, T2: SerializeRaw, T3: SerializeRaw, T4: SerializeRaw>(
         &mut self,
@@ -82,6 +191,8 @@ pub unsafe trait BufWrite: io::Write {
 }
 
 unsafe impl BufWrite for Vec<u8> {
+    const KEEPS_TAIL: bool = true;
+
     #[inline]
     fn next_ptr(&mut self) -> *mut u8 {
         unsafe { self.as_mut_ptr().add(self.len()) }
@@ -108,4 +219,139 @@ unsafe impl BufWrite for Vec<u8> {
     unsafe fn shrink(&mut self, shrink_size: usize) {
         self.set_len(self.len() - shrink_size);
     }
+
+    #[inline]
+    fn max_reserve(&self) -> usize {
+        // reservations within the spare capacity never reallocate, and beyond
+        // it, pieces of 4096 bytes avoid reserving up to six times the output
+        // length of a long string at once
+        cmp::max(self.capacity() - self.len(), 4096)
+    }
+}
+
+/// `BufWrite` over memory which has been reserved in advance
+///
+/// This writer never checks capacity, so that values written through
+/// `SerializeRaw::write_to_ptr` can reuse functions generic over `BufWrite`.
+pub(crate) struct PtrWriter {
+    ptr: *mut u8,
+    #[cfg(debug_assertions)]
+    end: *mut u8,
+}
+
+impl PtrWriter {
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `len` bytes, and the caller must not
+    /// write more than `len` bytes through this writer.
+    #[inline]
+    #[allow(unused_variables)]
+    pub unsafe fn new(ptr: *mut u8, len: usize) -> PtrWriter {
+        PtrWriter {
+            ptr,
+            #[cfg(debug_assertions)]
+            end: ptr.add(len),
+        }
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl io::Write for PtrWriter {
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.write_all(data).map(|_| data.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        #[cfg(debug_assertions)]
+        debug_assert!(data.len() <= self.end as usize - self.ptr as usize);
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.ptr, data.len());
+            self.ptr = self.ptr.add(data.len());
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl BufWrite for PtrWriter {
+    const KEEPS_TAIL: bool = true;
+
+    #[inline]
+    fn next_ptr(&mut self) -> *mut u8 {
+        self.ptr
+    }
+
+    #[inline]
+    fn reserve(&mut self, _additional: usize) -> Result<(), io::Error> {
+        #[cfg(debug_assertions)]
+        debug_assert!(_additional <= self.end as usize - self.ptr as usize);
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn advance(&mut self, additional: usize) {
+        self.ptr = self.ptr.add(additional);
+    }
+
+    #[inline]
+    unsafe fn shrink(&mut self, shrink_size: usize) {
+        self.ptr = self.ptr.sub(shrink_size);
+    }
+}
+
+/// Temporary output memory for writers which cannot reserve space in place
+///
+/// Small reservations are served by an inline array, and larger ones (e.g.
+/// long field names) by a lazily allocated heap buffer.
+pub(crate) struct Scratch {
+    inline: [u8; Scratch::INLINE_SIZE],
+    heap: Vec<u8>,
+    on_heap: bool,
+}
+
+impl Scratch {
+    pub const INLINE_SIZE: usize = 96;
+
+    #[inline]
+    pub fn new() -> Scratch {
+        Scratch {
+            inline: [0; Scratch::INLINE_SIZE],
+            heap: Vec::new(),
+            on_heap: false,
+        }
+    }
+
+    /// Selects a buffer which is at least `len` bytes long
+    ///
+    /// The contents of the selected buffer are unspecified.
+    #[inline]
+    pub fn prepare(&mut self, len: usize) {
+        if likely!(len <= Scratch::INLINE_SIZE) {
+            self.on_heap = false;
+        } else {
+            if self.heap.len() < len {
+                self.heap.resize(len, 0);
+            }
+            self.on_heap = true;
+        }
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.on_heap {
+            &mut self.heap
+        } else {
+            &mut self.inline
+        }
+    }
 }
@@ -1,7 +1,11 @@
 use crate::bufwrite::BufWrite;
+use crate::suffix::MAX_SUFFIX_LEN;
 
 use core::cmp;
+use core::fmt;
+use core::mem::ManuallyDrop;
 use core::ptr;
+use std::error;
 use std::io;
 use std::io::Write;
 
@@ -11,27 +15,91 @@ use std::io::Write;
 const DEFAULT_BUFFER_SIZE: usize = 8192 + 256;
 const MIN_BUFFER_SIZE: usize = 48;
 
-pub struct BufWriter<'a> {
-    inner: Box<dyn 'a + Write>,
+/// Buffered writer optimized for JSON serialization
+///
+/// Unlike `std::io::BufWriter`, this writer exposes its buffer to the
+/// serializer through [`BufWrite`], so that values are formatted directly
+/// into the buffer.
+///
+/// Buffered data is written to the inner writer when the `BufWriter` is
+/// dropped, but errors are ignored in that case. Use [`into_inner`] or
+/// [`flush`] to handle them.
+///
+/// [`into_inner`]: BufWriter::into_inner
+/// [`flush`]: std::io::Write::flush
+pub struct BufWriter<W: Write> {
+    inner: W,
     buf: Vec<u8>,
 }
 
-impl<'a> BufWriter<'a> {
+impl<W: Write> BufWriter<W> {
+    /// Creates a new `BufWriter` with the default buffer capacity
     #[inline]
-    pub fn new<W: 'a + Write>(inner: W) -> BufWriter<'a> {
+    pub fn new(inner: W) -> BufWriter<W> {
         Self::with_capacity(DEFAULT_BUFFER_SIZE, inner)
     }
 
+    /// Creates a new `BufWriter` with at least the specified buffer capacity
+    ///
+    /// Capacity smaller than 48 bytes is rounded up. The buffer grows only if
+    /// a single value such as a field name does not fit in it.
     #[inline]
-    pub fn with_capacity<W: 'a + Write>(mut capacity: usize, inner: W) -> BufWriter<'a> {
+    pub fn with_capacity(mut capacity: usize, inner: W) -> BufWriter<W> {
         capacity = cmp::max(MIN_BUFFER_SIZE, capacity);
 
         BufWriter {
-            inner: Box::new(inner),
+            inner,
             buf: Vec::with_capacity(capacity),
         }
     }
 
+    /// Gets a reference to the inner writer
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the inner writer
+    ///
+    /// Writing directly to the inner writer may corrupt the output because
+    /// the buffered data has not been written yet.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Returns the data which has not been written to the inner writer yet
+    #[inline]
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Returns the buffer capacity
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Writes the buffered data and returns the inner writer
+    ///
+    /// If writing fails, the `BufWriter` is returned back along with the error
+    /// so that no data is lost.
+    pub fn into_inner(mut self) -> Result<W, IntoInnerError<BufWriter<W>>> {
+        match self.flush_buf() {
+            Ok(()) => Ok(self.into_parts().0),
+            Err(e) => Err(IntoInnerError(self, e)),
+        }
+    }
+
+    /// Returns the inner writer and the data which has not been written yet
+    ///
+    /// No data is written to the inner writer by this method.
+    pub fn into_parts(self) -> (W, Vec<u8>) {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped after fields are moved out
+        unsafe { (ptr::read(&this.inner), ptr::read(&this.buf)) }
+    }
+
     #[inline]
     unsafe fn write_to_buf_unchecked(&mut self, data: &[u8]) {
         let old_len = self.buf.len();
@@ -45,16 +113,46 @@ impl<'a> BufWriter<'a> {
     #[cold]
     #[inline(never)]
     fn write_slow(&mut self, data: &[u8]) -> io::Result<()> {
-        tri!(self.flush_buf());
-
-        if data.len() <= self.buf.capacity() {
+        if data.len() <= self.buf.capacity() - MAX_SUFFIX_LEN {
+            tri!(self.flush_head());
             unsafe {
                 self.write_to_buf_unchecked(data);
             }
             Ok(())
         } else {
-            self.inner.write_all(data)
+            // Large data bypasses the buffer, but its trailing bytes are kept
+            // in the buffer so that they still can be shrunk.
+            let (head, tail) = data.split_at(data.len() - MAX_SUFFIX_LEN);
+            tri!(self.flush_buf());
+            tri!(self.inner.write_all(head));
+            unsafe {
+                self.write_to_buf_unchecked(tail);
+            }
+            Ok(())
+        }
+    }
+
+    /// Flush buffer contents except for the last `MAX_SUFFIX_LEN` bytes
+    ///
+    /// Serializers write a separator after each element and remove it by
+    /// `BufWrite::shrink` when the container ends, so those bytes must not
+    /// reach the inner writer until another value has been written.
+    #[cold]
+    #[inline(never)]
+    fn flush_head(&mut self) -> io::Result<()> {
+        let len = self.buf.len();
+        if len <= MAX_SUFFIX_LEN {
+            return Ok(());
         }
+
+        let head_len = len - MAX_SUFFIX_LEN;
+        tri!(self.inner.write_all(&self.buf[..head_len]));
+        unsafe {
+            let ptr = self.buf.as_mut_ptr();
+            ptr::copy(ptr.add(head_len), ptr, MAX_SUFFIX_LEN);
+            self.buf.set_len(MAX_SUFFIX_LEN);
+        }
+        Ok(())
     }
 
     pub(crate) fn flush_buf(&mut self) -> io::Result<()> {
@@ -64,7 +162,7 @@ impl<'a> BufWriter<'a> {
     }
 }
 
-impl<'a> Write for BufWriter<'a> {
+impl<W: Write> Write for BufWriter<W> {
     #[inline]
     fn write(&mut self, data: &[u8]) -> io::Result<usize> {
         self.write_all(data).map(|_| data.len())
@@ -87,7 +185,9 @@ impl<'a> Write for BufWriter<'a> {
     }
 }
 
-unsafe impl<'a> BufWrite for BufWriter<'a> {
+unsafe impl<W: Write> BufWrite for BufWriter<W> {
+    const KEEPS_TAIL: bool = true;
+
     #[inline]
     fn next_ptr(&mut self) -> *mut u8 {
         self.buf.next_ptr()
@@ -98,14 +198,16 @@ unsafe impl<'a> BufWrite for BufWriter<'a> {
         // SAFETY: this operation won't overflow because slice cannot exceeds isize::MAX bytes.
         // https://doc.rust-lang.org/reference/behavior-considered-undefined.html
         if likely!(self.buf.len() + additional <= self.buf.capacity()) {
-            Ok(())
-        } else if likely!(additional <= self.buf.capacity()) {
-            self.flush_buf()
-        } else {
-            // this case should never happen in this crate. all input data
-            // must be shorter than MIN_BUFFER_SIZE.
-            Err(io::Error::new(io::ErrorKind::Other, "capacity overflow"))
+            return Ok(());
         }
+
+        tri!(self.flush_head());
+        if unlikely!(self.buf.len() + additional > self.buf.capacity()) {
+            // a single value (e.g. a long field name) is larger than the
+            // buffer, so grow the buffer instead of failing.
+            Vec::reserve(&mut self.buf, additional);
+        }
+        Ok(())
     }
 
     #[inline]
@@ -115,21 +217,87 @@ unsafe impl<'a> BufWrite for BufWriter<'a> {
 
     #[inline]
     unsafe fn shrink(&mut self, shrink_size: usize) {
+        debug_assert!(shrink_size <= self.buf.len());
         self.buf.shrink(shrink_size);
     }
+
+    #[inline]
+    fn max_reserve(&self) -> usize {
+        self.buf.capacity() - MAX_SUFFIX_LEN
+    }
 }
 
-impl<'a> Drop for BufWriter<'a> {
+impl<W: Write> Drop for BufWriter<W> {
     fn drop(&mut self) {
         let _ = self.flush_buf();
     }
 }
 
+impl<W: Write + fmt::Debug> fmt::Debug for BufWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufWriter")
+            .field("inner", &self.inner)
+            .field("buffer", &format_args!("{}/{}", self.buf.len(), self.buf.capacity()))
+            .finish()
+    }
+}
+
+/// An error returned by [`BufWriter::into_inner`]
+///
+/// It contains the writer which failed to flush, so that the buffered data can
+/// be recovered.
+#[derive(Debug)]
+pub struct IntoInnerError<W>(W, io::Error);
+
+impl<W> IntoInnerError<W> {
+    /// Returns the error which caused the flush to fail
+    #[inline]
+    pub fn error(&self) -> &io::Error {
+        &self.1
+    }
+
+    /// Returns the writer which failed to flush
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+
+    /// Consumes the `IntoInnerError` and returns the underlying error
+    #[inline]
+    pub fn into_error(self) -> io::Error {
+        self.1
+    }
+
+    /// Returns both the writer and the error
+    #[inline]
+    pub fn into_parts(self) -> (io::Error, W) {
+        (self.1, self.0)
+    }
+}
+
+impl<W> From<IntoInnerError<W>> for io::Error {
+    #[inline]
+    fn from(e: IntoInnerError<W>) -> io::Error {
+        e.1
+    }
+}
+
+impl<W> fmt::Display for IntoInnerError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.1.fmt(f)
+    }
+}
+
+impl<W: fmt::Debug> error::Error for IntoInnerError<W> {}
+
 #[cfg(test)]
 mod tests {
     use super::{BufWriter, MIN_BUFFER_SIZE};
     use crate::bufwrite::BufWrite;
-    use std::io::Write;
+    use crate::Serializer;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+    use std::io::{self, Write};
 
     #[test]
     fn write() {
@@ -138,20 +306,24 @@ mod tests {
         assert_eq!(writer.buf, &[]);
         assert_eq!(writer.buf.capacity(), MIN_BUFFER_SIZE);
 
-        writer.write(&[]).unwrap();
+        writer.write_all(&[]).unwrap();
         assert_eq!(writer.buf, &[]);
         assert_eq!(writer.buf.capacity(), MIN_BUFFER_SIZE);
 
-        writer.write(b"0000").unwrap();
+        writer.write_all(b"0000").unwrap();
         assert_eq!(writer.buf, b"0000");
         assert_eq!(writer.buf.capacity(), MIN_BUFFER_SIZE);
 
         let remain = writer.buf.capacity() - writer.buf.len();
-        writer.write(b"1".repeat(remain).as_slice()).unwrap();
+        writer.write_all(b"1".repeat(remain).as_slice()).unwrap();
         assert_eq!(writer.buf.len(), MIN_BUFFER_SIZE);
 
-        writer.write(b"2").unwrap();
-        assert_eq!(writer.buf, b"2");
+        writer.write_all(b"2").unwrap();
+        assert_eq!(writer.buf, b"112");
+        assert_eq!(writer.buf.capacity(), MIN_BUFFER_SIZE);
+
+        writer.write_all(b"3".repeat(MIN_BUFFER_SIZE).as_slice()).unwrap();
+        assert_eq!(writer.buf, b"33");
         assert_eq!(writer.buf.capacity(), MIN_BUFFER_SIZE);
 
         writer.flush().unwrap();
@@ -165,10 +337,173 @@ mod tests {
         let mut writer = BufWriter::with_capacity(MIN_BUFFER_SIZE + 1, inner);
 
         assert_eq!(writer.reserve(MIN_BUFFER_SIZE + 1).ok(), Some(()));
-        assert_eq!(writer.reserve(MIN_BUFFER_SIZE + 2).ok(), None);
+        assert_eq!(writer.capacity(), MIN_BUFFER_SIZE + 1);
+
+        writer.write_all(b"#".repeat(MIN_BUFFER_SIZE).as_slice()).unwrap();
+        assert_eq!(writer.reserve(MIN_BUFFER_SIZE - 1).ok(), Some(()));
+        assert_eq!(writer.buf, b"##");
+        assert_eq!(writer.capacity(), MIN_BUFFER_SIZE + 1);
 
-        writer.write(b"@").unwrap();
+        writer.write_all(b"@").unwrap();
         assert_eq!(writer.reserve(MIN_BUFFER_SIZE + 1).ok(), Some(()));
-        assert_eq!(writer.buf, b"");
+        assert_eq!(writer.buf, b"#@");
+        assert!(writer.capacity() >= MIN_BUFFER_SIZE + 3);
+    }
+
+    #[test]
+    fn into_inner() {
+        let mut writer = BufWriter::with_capacity(MIN_BUFFER_SIZE, Vec::new());
+        writer.write_all(b"abc").unwrap();
+        assert_eq!(writer.buffer(), b"abc");
+        assert!(writer.get_ref().is_empty());
+        assert_eq!(writer.into_inner().unwrap(), b"abc");
+
+        let mut writer = BufWriter::new(Vec::new());
+        writer.write_all(b"abc").unwrap();
+        let (inner, buf) = writer.into_parts();
+        assert!(inner.is_empty());
+        assert_eq!(buf, b"abc");
+    }
+
+    #[derive(Debug)]
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn into_inner_error() {
+        let mut writer = BufWriter::new(Broken);
+        writer.write_all(b"abc").unwrap();
+
+        let err = writer.into_inner().unwrap_err();
+        assert_eq!(err.error().kind(), io::ErrorKind::BrokenPipe);
+        let (_, buf) = err.into_inner().into_parts();
+        assert_eq!(buf, b"abc");
+    }
+
+    #[derive(serde_derive::Serialize)]
+    struct Node {
+        id: u32,
+        name: String,
+        children: Vec<Node>,
+        attrs: BTreeMap<String, (i64, Option<f64>)>,
+        empty: Empty,
+        unit: (),
+    }
+
+    #[derive(serde_derive::Serialize)]
+    struct Empty {}
+
+    fn tree(depth: u32, id: u32) -> Node {
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            (0..3).map(|i| tree(depth - 1, id * 3 + i)).collect()
+        };
+        let attrs = (0..id % 4)
+            .map(|i| (format!("k\"{}", i).repeat(i as usize), (-(i as i64), None)))
+            .collect();
+
+        Node {
+            id,
+            name: "n\t".repeat(id as usize % 20),
+            children,
+            attrs,
+            empty: Empty {},
+            unit: (),
+        }
+    }
+
+    fn to_vec_with_capacity<T: Serialize + ?Sized>(value: &T, capacity: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        {
+            let mut writer = BufWriter::with_capacity(capacity, &mut out);
+            value.serialize(&mut Serializer::new(&mut writer)).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn flush_nested() {
+        let value = tree(5, 1);
+        let expected = crate::to_vec(&value).unwrap();
+
+        for capacity in MIN_BUFFER_SIZE..MIN_BUFFER_SIZE * 3 {
+            let actual = to_vec_with_capacity(&value, capacity);
+            assert_eq!(
+                String::from_utf8(actual).unwrap(),
+                String::from_utf8_lossy(&expected),
+                "capacity = {}",
+                capacity
+            );
+        }
+    }
+
+    #[test]
+    fn flush_nested_seq() {
+        let long = "x".repeat(MIN_BUFFER_SIZE * 2);
+        type Row<'a> = Vec<(Vec<Empty>, &'a str, Vec<u8>)>;
+        let value: Vec<Row> = (0..40)
+            .map(|i| {
+                (0..i % 5)
+                    .map(|j| {
+                        let s = if j % 2 == 0 { long.as_str() } else { "" };
+                        ((0..j).map(|_| Empty {}).collect(), s, vec![j as u8; i])
+                    })
+                    .collect()
+            })
+            .collect();
+        let expected = crate::to_vec(&value).unwrap();
+
+        for capacity in MIN_BUFFER_SIZE..MIN_BUFFER_SIZE * 3 {
+            assert_eq!(to_vec_with_capacity(&value, capacity), expected);
+        }
+    }
+    #[test]
+    fn flush_deep() {
+        #[derive(serde_derive::Serialize)]
+        struct Level {
+            depth: u32,
+            text: String,
+            nodes: Vec<Node>,
+            next: Vec<Level>,
+        }
+
+        fn chain(depth: u32) -> Level {
+            let len = MIN_BUFFER_SIZE - 2 + depth as usize % 5;
+            let next = match depth {
+                0 => Vec::new(),
+                _ => vec![chain(depth - 1)],
+            };
+            Level {
+                depth,
+                text: "\"".repeat(depth as usize % 3) + &"y".repeat(len),
+                nodes: (0..depth % 3).map(|i| tree(i, depth)).collect(),
+                next,
+            }
+        }
+
+        let value = chain(300);
+        let expected = crate::to_vec(&value).unwrap();
+        assert!(expected.len() > MIN_BUFFER_SIZE * 500);
+
+        let mut out = Vec::new();
+        {
+            let mut writer = BufWriter::with_capacity(MIN_BUFFER_SIZE, &mut out);
+            value.serialize(&mut Serializer::new(&mut writer)).unwrap();
+            assert_eq!(writer.capacity(), MIN_BUFFER_SIZE);
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            String::from_utf8_lossy(&expected)
+        );
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,253 @@
+//! Byte sequences for `serialize_bytes` and `collect_seq`
+
+use crate::bufwrite::BufWrite;
+use crate::options::BytesEncoding;
+
+use core::any::TypeId;
+use core::marker::PhantomData;
+use core::{cmp, mem, slice};
+use std::io;
+use std::ptr::copy_nonoverlapping;
+
+static BASE64: [u8; 64] =
+    *b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+static BASE64_URL: [u8; 64] =
+    *b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+static HEX_LOWER: [u8; 16] = *b"0123456789abcdef";
+static HEX_UPPER: [u8; 16] = *b"0123456789ABCDEF";
+
+/// Decimal digits of each byte followed by a comma, padded to 4 bytes
+#[rustfmt::skip]
+static DEC_COMMA: [[u8; 4]; 256] = [
+    *b"0,\0\0", *b"1,\0\0", *b"2,\0\0", *b"3,\0\0", *b"4,\0\0", *b"5,\0\0", *b"6,\0\0", *b"7,\0\0",
+    *b"8,\0\0", *b"9,\0\0", *b"10,\0", *b"11,\0", *b"12,\0", *b"13,\0", *b"14,\0", *b"15,\0",
+    *b"16,\0", *b"17,\0", *b"18,\0", *b"19,\0", *b"20,\0", *b"21,\0", *b"22,\0", *b"23,\0",
+    *b"24,\0", *b"25,\0", *b"26,\0", *b"27,\0", *b"28,\0", *b"29,\0", *b"30,\0", *b"31,\0",
+    *b"32,\0", *b"33,\0", *b"34,\0", *b"35,\0", *b"36,\0", *b"37,\0", *b"38,\0", *b"39,\0",
+    *b"40,\0", *b"41,\0", *b"42,\0", *b"43,\0", *b"44,\0", *b"45,\0", *b"46,\0", *b"47,\0",
+    *b"48,\0", *b"49,\0", *b"50,\0", *b"51,\0", *b"52,\0", *b"53,\0", *b"54,\0", *b"55,\0",
+    *b"56,\0", *b"57,\0", *b"58,\0", *b"59,\0", *b"60,\0", *b"61,\0", *b"62,\0", *b"63,\0",
+    *b"64,\0", *b"65,\0", *b"66,\0", *b"67,\0", *b"68,\0", *b"69,\0", *b"70,\0", *b"71,\0",
+    *b"72,\0", *b"73,\0", *b"74,\0", *b"75,\0", *b"76,\0", *b"77,\0", *b"78,\0", *b"79,\0",
+    *b"80,\0", *b"81,\0", *b"82,\0", *b"83,\0", *b"84,\0", *b"85,\0", *b"86,\0", *b"87,\0",
+    *b"88,\0", *b"89,\0", *b"90,\0", *b"91,\0", *b"92,\0", *b"93,\0", *b"94,\0", *b"95,\0",
+    *b"96,\0", *b"97,\0", *b"98,\0", *b"99,\0", *b"100,", *b"101,", *b"102,", *b"103,",
+    *b"104,", *b"105,", *b"106,", *b"107,", *b"108,", *b"109,", *b"110,", *b"111,",
+    *b"112,", *b"113,", *b"114,", *b"115,", *b"116,", *b"117,", *b"118,", *b"119,",
+    *b"120,", *b"121,", *b"122,", *b"123,", *b"124,", *b"125,", *b"126,", *b"127,",
+    *b"128,", *b"129,", *b"130,", *b"131,", *b"132,", *b"133,", *b"134,", *b"135,",
+    *b"136,", *b"137,", *b"138,", *b"139,", *b"140,", *b"141,", *b"142,", *b"143,",
+    *b"144,", *b"145,", *b"146,", *b"147,", *b"148,", *b"149,", *b"150,", *b"151,",
+    *b"152,", *b"153,", *b"154,", *b"155,", *b"156,", *b"157,", *b"158,", *b"159,",
+    *b"160,", *b"161,", *b"162,", *b"163,", *b"164,", *b"165,", *b"166,", *b"167,",
+    *b"168,", *b"169,", *b"170,", *b"171,", *b"172,", *b"173,", *b"174,", *b"175,",
+    *b"176,", *b"177,", *b"178,", *b"179,", *b"180,", *b"181,", *b"182,", *b"183,",
+    *b"184,", *b"185,", *b"186,", *b"187,", *b"188,", *b"189,", *b"190,", *b"191,",
+    *b"192,", *b"193,", *b"194,", *b"195,", *b"196,", *b"197,", *b"198,", *b"199,",
+    *b"200,", *b"201,", *b"202,", *b"203,", *b"204,", *b"205,", *b"206,", *b"207,",
+    *b"208,", *b"209,", *b"210,", *b"211,", *b"212,", *b"213,", *b"214,", *b"215,",
+    *b"216,", *b"217,", *b"218,", *b"219,", *b"220,", *b"221,", *b"222,", *b"223,",
+    *b"224,", *b"225,", *b"226,", *b"227,", *b"228,", *b"229,", *b"230,", *b"231,",
+    *b"232,", *b"233,", *b"234,", *b"235,", *b"236,", *b"237,", *b"238,", *b"239,",
+    *b"240,", *b"241,", *b"242,", *b"243,", *b"244,", *b"245,", *b"246,", *b"247,",
+    *b"248,", *b"249,", *b"250,", *b"251,", *b"252,", *b"253,", *b"254,", *b"255,",
+];
+
+/// Writes `bytes` as an array of numbers followed by `suffix`
+pub(crate) fn write_array<B: BufWrite>(
+    buf: &mut B,
+    bytes: &[u8],
+    suffix: &'static str,
+) -> io::Result<()> {
+    // `len * 4 + 2` bytes in a single reservation unless the array is longer
+    // than the writer can reserve
+    let chunk_len = cmp::max(buf.max_reserve().saturating_sub(2 + suffix.len()) / 4, 1);
+    let mut chunks = bytes.chunks(chunk_len);
+    let mut chunk = chunks.next().unwrap_or(&[]);
+    let mut open = true;
+
+    loop {
+        let next = chunks.next();
+        tri!(buf.reserve(chunk.len() * 4 + 2 + suffix.len()));
+        unsafe {
+            let start = buf.next_ptr();
+            let mut dst = start;
+            if open {
+                *dst = b'[';
+                dst = dst.add(1);
+                open = false;
+            }
+            for &byte in chunk {
+                copy_nonoverlapping(DEC_COMMA[byte as usize].as_ptr(), dst, 4);
+                dst = dst.add(2 + (byte >= 10) as usize + (byte >= 100) as usize);
+            }
+            if next.is_none() {
+                // replaces the last comma, or follows `[` for an empty array
+                if !bytes.is_empty() {
+                    dst = dst.sub(1);
+                }
+                *dst = b']';
+                copy_nonoverlapping(suffix.as_ptr(), dst.add(1), suffix.len());
+                dst = dst.add(1 + suffix.len());
+            }
+            buf.advance(dst as usize - start as usize);
+        }
+
+        match next {
+            Some(next) => chunk = next,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Returns the remaining bytes if `iter` is a `slice::Iter<u8>`, which is how
+/// serde passes `[u8]` and `Vec<u8>` to `collect_seq`
+#[inline]
+pub(crate) fn as_byte_slice<I: Iterator>(iter: &I) -> Option<&[u8]> {
+    if type_id::<I>() == type_id::<slice::Iter<'static, u8>>() {
+        // SAFETY: `I` is `slice::Iter<u8>` with some lifetime
+        let iter = unsafe { &*(iter as *const I as *const slice::Iter<u8>) };
+        Some(iter.as_slice())
+    } else {
+        None
+    }
+}
+
+/// `TypeId` of `T` with its lifetimes erased
+fn type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = PhantomData::<T>;
+    // SAFETY: `type_id` does not depend on lifetimes
+    let any = unsafe {
+        mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom)
+    };
+    any.type_id()
+}
+
+/// Writes `bytes` in `encoding`, without quotes
+///
+/// `encoding` must not be `BytesEncoding::Array`, which the serializers write
+/// as a sequence.
+pub(crate) fn write_encoded<B: BufWrite>(
+    buf: &mut B,
+    bytes: &[u8],
+    encoding: BytesEncoding,
+) -> io::Result<()> {
+    match encoding {
+        BytesEncoding::Array => unreachable!("byte arrays are written as sequences"),
+        BytesEncoding::Base64 => write_base64(buf, bytes, &BASE64, true),
+        BytesEncoding::Base64UrlNoPad => write_base64(buf, bytes, &BASE64_URL, false),
+        BytesEncoding::HexLower => write_hex(buf, bytes, &HEX_LOWER),
+        BytesEncoding::HexUpper => write_hex(buf, bytes, &HEX_UPPER),
+    }
+}
+
+/// Encodes `bytes` in chunks which fit in a single reservation
+fn write_base64<B: BufWrite>(
+    buf: &mut B,
+    bytes: &[u8],
+    alphabet: &[u8; 64],
+    pad: bool,
+) -> io::Result<()> {
+    // a multiple of 3 bytes, so that only the last chunk is padded
+    let chunk_len = cmp::max(buf.max_reserve() / 4, 1) * 3;
+
+    for chunk in bytes.chunks(chunk_len) {
+        let len = match chunk.len() % 3 {
+            0 => chunk.len() / 3 * 4,
+            _ if pad => (chunk.len() / 3 + 1) * 4,
+            rem => chunk.len() / 3 * 4 + rem + 1,
+        };
+
+        tri!(buf.reserve(len));
+        unsafe {
+            encode_base64(chunk, buf.next_ptr(), alphabet, pad);
+            buf.advance(len);
+        }
+    }
+
+    Ok(())
+}
+
+#[inline]
+unsafe fn encode_base64(input: &[u8], dst: *mut u8, alphabet: &[u8; 64], pad: bool) {
+    #[inline(always)]
+    unsafe fn encode_group(n: u32, dst: *mut u8, alphabet: &[u8; 64]) {
+        *dst = alphabet[(n >> 18) as usize & 0x3F];
+        *dst.add(1) = alphabet[(n >> 12) as usize & 0x3F];
+        *dst.add(2) = alphabet[(n >> 6) as usize & 0x3F];
+        *dst.add(3) = alphabet[n as usize & 0x3F];
+    }
+
+    let mut dst = dst;
+
+    // 12 bytes become 16 bytes, which lets the compiler unroll the loop
+    let mut blocks = input.chunks_exact(12);
+    for block in &mut blocks {
+        for i in 0..4 {
+            let g = &block[i * 3..i * 3 + 3];
+            let n = (g[0] as u32) << 16 | (g[1] as u32) << 8 | g[2] as u32;
+            encode_group(n, dst.add(i * 4), alphabet);
+        }
+        dst = dst.add(16);
+    }
+
+    let mut groups = blocks.remainder().chunks_exact(3);
+    for g in &mut groups {
+        let n = (g[0] as u32) << 16 | (g[1] as u32) << 8 | g[2] as u32;
+        encode_group(n, dst, alphabet);
+        dst = dst.add(4);
+    }
+
+    let (n, len) = match *groups.remainder() {
+        [a] => ((a as u32) << 16, 2),
+        [a, b] => ((a as u32) << 16 | (b as u32) << 8, 3),
+        _ => return,
+    };
+
+    let mut tail = [0; 4];
+    encode_group(n, tail.as_mut_ptr(), alphabet);
+    if pad {
+        tail[len..].iter_mut().for_each(|b| *b = b'=');
+        std::ptr::copy_nonoverlapping(tail.as_ptr(), dst, 4);
+    } else {
+        std::ptr::copy_nonoverlapping(tail.as_ptr(), dst, len);
+    }
+}
+
+/// Encodes `bytes` in chunks which fit in a single reservation
+fn write_hex<B: BufWrite>(
+    buf: &mut B,
+    bytes: &[u8],
+    digits: &[u8; 16],
+) -> io::Result<()> {
+    let chunk_len = cmp::max(buf.max_reserve() / 2, 1);
+
+    for chunk in bytes.chunks(chunk_len) {
+        tri!(buf.reserve(chunk.len() * 2));
+        unsafe {
+            let dst = buf.next_ptr();
+            for (i, &byte) in chunk.iter().enumerate() {
+                *dst.add(i * 2) = digits[(byte >> 4) as usize];
+                *dst.add(i * 2 + 1) = digits[(byte & 0xF) as usize];
+            }
+            buf.advance(chunk.len() * 2);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::bufwrite::{BufWrite, Scratch};
+use crate::suffix::MAX_SUFFIX_LEN;
+
+use std::io;
+
+/// `BufWrite` implementation which only counts the output length
+///
+/// Values formatted via `SerializeRaw` are written to a scratch buffer and
+/// discarded, so the count is exact rather than the sum of size hints.
+pub(crate) struct CountWriter {
+    count: usize,
+    /// The first `MAX_SUFFIX_LEN` bytes stand for the previous output so that
+    /// serializers can rewrite trailing separators
+    scratch: Scratch,
+}
+
+impl CountWriter {
+    #[inline]
+    pub fn new() -> CountWriter {
+        CountWriter {
+            count: 0,
+            scratch: Scratch::new(),
+        }
+    }
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl io::Write for CountWriter {
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.count += data.len();
+        Ok(data.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.count += data.len();
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl BufWrite for CountWriter {
+    const KEEPS_TAIL: bool = true;
+
+    #[inline]
+    fn next_ptr(&mut self) -> *mut u8 {
+        unsafe { self.scratch.as_mut_slice().as_mut_ptr().add(MAX_SUFFIX_LEN) }
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), io::Error> {
+        self.scratch.prepare(MAX_SUFFIX_LEN + additional);
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn advance(&mut self, additional: usize) {
+        self.count += additional;
+    }
+
+    #[inline]
+    unsafe fn shrink(&mut self, shrink_size: usize) {
+        debug_assert!(shrink_size <= MAX_SUFFIX_LEN);
+        self.count -= shrink_size;
+    }
+
+    #[inline]
+    fn max_reserve(&self) -> usize {
+        Scratch::INLINE_SIZE - MAX_SUFFIX_LEN
+    }
+}
@@ -7,6 +7,15 @@ pub enum Error {
     // TODO: print location where error happened.
     //       e.g. `Invalid key type at "KeyA.KeyB.KeyC"`
     InvalidKey,
+    /// The output buffer passed to [`to_slice`](crate::to_slice) is too small
+    ///
+    /// `required` is the length of the whole output if it is known.
+    BufferTooSmall {
+        required: Option<usize>,
+    },
+    /// A NaN or infinite float was serialized with
+    /// [`NonFinitePolicy::Error`](crate::NonFinitePolicy::Error)
+    NonFiniteFloat,
     Custom(String),
     #[cfg(feature = "std")]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
@@ -17,6 +26,11 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::InvalidKey => f.pad("Invalid key type for JSON"),
+            Error::BufferTooSmall { required: None } => f.pad("Output buffer is too small"),
+            Error::BufferTooSmall {
+                required: Some(required),
+            } => write!(f, "Output buffer is too small ({} bytes required)", required),
+            Error::NonFiniteFloat => f.pad("Non-finite float is not allowed in JSON"),
             Error::Custom(ref s) => f.pad(s.as_str()),
             #[cfg(feature = "std")]
             Error::Io(ref err) => err.fmt(f),
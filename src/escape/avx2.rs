@@ -1,4 +1,5 @@
-use super::common::{u_encode, BS, ESCAPED, QU, UU};
+use super::common::{u_encode, write_rest, BS, ESCAPED, QU, UU};
+use super::table::Escaper;
 use crate::bufwrite::BufWrite;
 
 use std::io;
@@ -9,6 +10,7 @@ use std::arch::x86::*;
 use std::arch::x86_64::*;
 
 #[inline]
+#[target_feature(enable = "avx2")]
 unsafe fn memcpy_16_32(src: *const u8, dst: *mut u8, len: usize) {
     debug_assert!(len >= 16);
     debug_assert!(len <= 32);
@@ -21,6 +23,7 @@ unsafe fn memcpy_16_32(src: *const u8, dst: *mut u8, len: usize) {
 }
 
 #[inline]
+#[target_feature(enable = "avx2")]
 unsafe fn escape_middle<W: BufWrite>(buf: &mut W, feed: &str) -> io::Result<()> {
     debug_assert!(feed.len() >= 16);
     debug_assert!(feed.len() <= 32);
@@ -80,6 +83,7 @@ unsafe fn escape_middle<W: BufWrite>(buf: &mut W, feed: &str) -> io::Result<()>
 }
 
 #[inline]
+#[target_feature(enable = "avx2")]
 unsafe fn escape_avx2<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
     debug_assert!(feed.len() >= 32);
 
@@ -161,12 +165,77 @@ unsafe fn escape_avx2<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
     }
 }
 
-pub(crate) fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
     if feed.len() <= 16 {
-        super::naive::escape(buf, feed)
+        super::sse2::escape(buf, feed)
     } else if feed.len() <= 32 {
-        unsafe { escape_middle(buf, feed) }
+        escape_middle(buf, feed)
     } else {
-        unsafe { escape_avx2(buf, feed) }
+        escape_avx2(buf, feed)
     }
 }
+
+/// Returns the mask of bytes in `v` which are set in the nibble lookup
+/// tables of an [`Escaper`]
+#[inline]
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn table_mask(v: __m256i, lut: &[[u8; 16]; 2]) -> u64 {
+    let v_lut_lo = _mm256_broadcastsi128_si256(_mm_loadu_si128(lut[0].as_ptr() as _));
+    let v_lut_hi = _mm256_broadcastsi128_si256(_mm_loadu_si128(lut[1].as_ptr() as _));
+    let v_bits = _mm256_broadcastsi128_si256(_mm_setr_epi8(
+        1, 2, 4, 8, 16, 32, 64, -128, 1, 2, 4, 8, 16, 32, 64, -128,
+    ));
+    let v_nibble = _mm256_set1_epi8(0xF);
+    let v_zero = _mm256_setzero_si256();
+
+    let lo = _mm256_and_si256(v, v_nibble);
+    let hi = _mm256_and_si256(_mm256_srli_epi16(v, 4), v_nibble);
+    // bytes from 0x80 use the second table
+    let row = _mm256_blendv_epi8(
+        _mm256_shuffle_epi8(v_lut_lo, lo),
+        _mm256_shuffle_epi8(v_lut_hi, lo),
+        v,
+    );
+    let hit = _mm256_and_si256(row, _mm256_shuffle_epi8(v_bits, hi));
+
+    !_mm256_movemask_epi8(_mm256_cmpeq_epi8(hit, v_zero)) as u32 as u64
+}
+
+/// Same as [`escape`], but escapes with `escaper`
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn escape_table<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    if feed.len() < 32 {
+        return super::sse2::escape_table(buf, feed, escaper);
+    }
+
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    while end_ptr as usize - ptr as usize >= 32 {
+        let mask = table_mask(_mm256_loadu_si256(ptr as _), &escaper.lut);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(32);
+    }
+
+    if ptr != end_ptr {
+        let last = end_ptr.sub(32);
+        let mask = table_mask(_mm256_loadu_si256(last as _), &escaper.lut)
+            >> (ptr as usize - last as usize);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    write_rest(buf, read_ptr, end_ptr)
+}
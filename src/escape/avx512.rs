@@ -0,0 +1,119 @@
+use super::common::{write_masked, write_rest, BS, QU};
+use super::table::Escaper;
+use crate::bufwrite::BufWrite;
+
+use std::io;
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Returns the mask of bytes in `v` which must be escaped
+#[inline]
+#[target_feature(enable = "avx2,avx512bw")]
+unsafe fn escape_mask(v: __m512i) -> u64 {
+    let v_bound = _mm512_set1_epi8(0x20);
+    let v_quot = _mm512_set1_epi8(QU[1] as i8);
+    let v_bs = _mm512_set1_epi8(BS[1] as i8);
+
+    _mm512_cmplt_epu8_mask(v, v_bound)
+        | _mm512_cmpeq_epi8_mask(v, v_quot)
+        | _mm512_cmpeq_epi8_mask(v, v_bs)
+}
+
+/// # Safety
+///
+/// The CPU must support AVX2 and AVX-512BW.
+#[target_feature(enable = "avx2,avx512bw")]
+pub(crate) unsafe fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+    if feed.len() < 64 {
+        return super::avx2::escape(buf, feed);
+    }
+
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    while end_ptr as usize - ptr as usize >= 64 {
+        let mask = escape_mask(_mm512_loadu_si512(ptr as _));
+        tri!(write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(64);
+    }
+
+    let remain = end_ptr as usize - ptr as usize;
+    if remain != 0 {
+        // masked-out bytes are never read, so this load does not cross the
+        // end of `feed`
+        let k = (1u64 << remain) - 1;
+        let mask = escape_mask(_mm512_maskz_loadu_epi8(k, ptr as _)) & k;
+        tri!(write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    if read_ptr < end_ptr {
+        let part =
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize);
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the mask of bytes in `v` which are set in the nibble lookup
+/// tables of an [`Escaper`]
+#[inline]
+#[target_feature(enable = "avx2,avx512bw")]
+unsafe fn table_mask(v: __m512i, lut: &[[u8; 16]; 2]) -> u64 {
+    let v_lut_lo = _mm512_broadcast_i32x4(_mm_loadu_si128(lut[0].as_ptr() as _));
+    let v_lut_hi = _mm512_broadcast_i32x4(_mm_loadu_si128(lut[1].as_ptr() as _));
+    let v_bits = _mm512_broadcast_i32x4(_mm_setr_epi8(
+        1, 2, 4, 8, 16, 32, 64, -128, 1, 2, 4, 8, 16, 32, 64, -128,
+    ));
+    let v_nibble = _mm512_set1_epi8(0xF);
+
+    let lo = _mm512_and_si512(v, v_nibble);
+    let hi = _mm512_and_si512(_mm512_srli_epi16(v, 4), v_nibble);
+    // bytes from 0x80 use the second table
+    let row = _mm512_mask_blend_epi8(
+        _mm512_movepi8_mask(v),
+        _mm512_shuffle_epi8(v_lut_lo, lo),
+        _mm512_shuffle_epi8(v_lut_hi, lo),
+    );
+
+    _mm512_test_epi8_mask(row, _mm512_shuffle_epi8(v_bits, hi))
+}
+
+/// Same as [`escape`], but escapes with `escaper`
+///
+/// # Safety
+///
+/// The CPU must support AVX2 and AVX-512BW.
+#[target_feature(enable = "avx2,avx512bw")]
+pub(crate) unsafe fn escape_table<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    if feed.len() < 64 {
+        return super::avx2::escape_table(buf, feed, escaper);
+    }
+
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    while end_ptr as usize - ptr as usize >= 64 {
+        let mask = table_mask(_mm512_loadu_si512(ptr as _), &escaper.lut);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(64);
+    }
+
+    let remain = end_ptr as usize - ptr as usize;
+    if remain != 0 {
+        let k = (1u64 << remain) - 1;
+        let mask = table_mask(_mm512_maskz_loadu_epi8(k, ptr as _), &escaper.lut) & k;
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    write_rest(buf, read_ptr, end_ptr)
+}
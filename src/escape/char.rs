@@ -1,5 +1,7 @@
 use super::common::{ESCAPED, UU};
+use super::Escaper;
 use crate::bufwrite::BufWrite;
+use crate::raw::SerializeRaw;
 
 use std::io;
 
@@ -13,18 +15,56 @@ const MAX_THREE_B: u32 = 0x10000;
 
 static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
 
-pub(crate) fn escape_char<W: BufWrite>(buf: &mut W, c: char) -> io::Result<()> {
-    tri!(buf.reserve(6));
-    let ptr = buf.next_ptr();
+pub(crate) fn escape_char<W: BufWrite>(
+    buf: &mut W,
+    c: char,
+    escaper: Option<&Escaper>,
+) -> io::Result<()> {
+    buf.write1(&EscapedChar(c, escaper))
+}
+
+/// Escaped character, without quotes
+pub(crate) struct EscapedChar<'a>(pub char, pub Option<&'a Escaper>);
+
+/// Escaped character enclosed in double quotes
+pub(crate) struct QuotedChar<'a>(pub char, pub Option<&'a Escaper>);
+
+impl<'a> SerializeRaw for EscapedChar<'a> {
+    #[inline]
+    fn size_hint(&self) -> usize {
+        // a surrogate pair is written for non-BMP characters if escaped
+        if self.1.is_none() {
+            6
+        } else {
+            12
+        }
+    }
+
+    #[inline]
+    unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
+        match self.1 {
+            None => escape_char_impl(dst, self.0),
+            Some(escaper) => escaper.escape_char_to_ptr(dst, self.0),
+        }
+    }
+}
 
-    unsafe {
-        let len = escape_char_impl(ptr, c);
-        buf.advance(len);
+impl<'a> SerializeRaw for QuotedChar<'a> {
+    #[inline]
+    fn size_hint(&self) -> usize {
+        EscapedChar(self.0, self.1).size_hint() + 2
     }
 
-    Ok(())
+    #[inline]
+    unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
+        *dst = b'"';
+        let len = EscapedChar(self.0, self.1).write_to_ptr(dst.add(1));
+        *dst.add(len + 1) = b'"';
+        len + 2
+    }
 }
 
+#[inline]
 unsafe fn escape_char_impl(dst: *mut u8, c: char) -> usize {
     let code = c as u32;
     if code < MAX_ONE_B {
@@ -12,7 +12,7 @@ pub(super) const UU: [u8; 2] = *b"\\u";
 const __: [u8; 2] = [0, 0];
 
 #[rustfmt::skip]
-pub(super) static ESCAPED: [[u8; 2]; 256] = [
+pub(crate) static ESCAPED: [[u8; 2]; 256] = [
     UU, UU, UU, UU, UU, UU, UU, UU, BB, TT, NN, UU, FF, RR, UU, UU,
     UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU, UU,
     __, __, QU, __, __, __, __, __, __, __, __, __, __, __, __, __,
@@ -44,3 +44,53 @@ pub(super) fn u_encode<B: BufWrite>(buf: &mut B, byte: u8) -> io::Result<()> {
     ];
     buf.write_all(&bytes)
 }
+
+/// Writes `[*read_ptr, ptr + i)` and the escaped byte at `ptr + i` for each
+/// bit `i` set in `mask`, then advances `read_ptr` past the last escaped byte
+///
+/// SIMD backends call this function with the comparison mask of each block.
+#[allow(dead_code)]
+#[inline]
+pub(super) unsafe fn write_masked<B: BufWrite>(
+    buf: &mut B,
+    ptr: *const u8,
+    mut mask: u64,
+    read_ptr: &mut *const u8,
+) -> io::Result<()> {
+    while mask != 0 {
+        let trailing_zeros = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        let ptr2 = ptr.add(trailing_zeros);
+        if *read_ptr < ptr2 {
+            let part =
+                std::slice::from_raw_parts(*read_ptr, ptr2 as usize - *read_ptr as usize);
+            tri!(buf.write_all(part));
+        }
+
+        *read_ptr = ptr2.add(1);
+        let key = ESCAPED[*ptr2 as usize];
+        if key != UU {
+            tri!(buf.write_all(&key[..]));
+        } else {
+            tri!(u_encode(buf, *ptr2));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `[read_ptr, end_ptr)` if it is not empty
+#[inline]
+pub(super) unsafe fn write_rest<B: BufWrite>(
+    buf: &mut B,
+    read_ptr: *const u8,
+    end_ptr: *const u8,
+) -> io::Result<()> {
+    if read_ptr < end_ptr {
+        let part =
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize);
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
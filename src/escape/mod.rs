@@ -2,35 +2,544 @@ mod char;
 mod common;
 mod naive;
 mod need_escape;
+mod str;
+mod swar;
+mod table;
 
-pub(crate) use self::char::escape_char;
+#[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+mod portable;
+
+pub(crate) use self::char::{escape_char, EscapedChar, QuotedChar};
+pub(crate) use self::str::{write_escaped, write_quoted};
+pub(crate) use self::common::ESCAPED;
+pub(crate) use self::table::Escaper;
 pub use need_escape::need_escape;
 
-#[cfg(all(
-    any(target_arch = "x86_64", target_arch = "x86"),
-    target_feature = "avx2",
-    not(miri)
-))]
+#[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
 mod avx2;
+#[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+// only compiled on Rust 1.89.0 or later (see build.rs)
+#[allow(clippy::incompatible_msrv)]
+mod avx512;
+#[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+mod sse2;
+
+use crate::bufwrite::BufWrite;
+
+use std::io;
+
+/// Escape implementations which may be selected at runtime
+///
+/// A `Backend` other than `Naive` must only be used after checking that the
+/// CPU supports it, which `Backend::current` and `Backend::available` do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum Backend {
+    Naive = 1,
+    Swar = 2,
+    #[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+    PortableSimd = 3,
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+    Sse2 = 4,
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+    Ssse3 = 5,
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+    Avx2 = 6,
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+    Avx512 = 7,
+}
+
+impl Backend {
+    /// Returns all backends supported by this CPU, from the slowest one
+    #[allow(dead_code)]
+    pub(crate) fn available() -> Vec<Backend> {
+        #[allow(unused_mut)]
+        let mut backends = vec![Backend::Naive, Backend::Swar];
+
+        #[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+        backends.push(Backend::PortableSimd);
+
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                backends.push(Backend::Sse2);
+            }
+            if is_x86_feature_detected!("ssse3") {
+                backends.push(Backend::Ssse3);
+            }
+            if is_x86_feature_detected!("avx2") {
+                backends.push(Backend::Avx2);
+            }
+        }
+
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("avx512bw") {
+                backends.push(Backend::Avx512);
+            }
+        }
+
+        backends
+    }
+
+    /// Returns the fastest backend supported by this CPU
+    ///
+    /// Detection is skipped if the fastest backend is enabled at compile time.
+    #[inline]
+    pub(crate) fn current() -> Backend {
+        #[cfg(all(
+            any(target_arch = "x86_64", target_arch = "x86"),
+            evil_json_1_89,
+            target_feature = "avx512bw",
+            not(miri)
+        ))]
+        {
+            Backend::Avx512
+        }
+
+        #[cfg(all(
+            any(target_arch = "x86_64", target_arch = "x86"),
+            not(evil_json_1_89),
+            target_feature = "avx2",
+            not(miri)
+        ))]
+        {
+            Backend::Avx2
+        }
 
-#[cfg(not(all(
-    any(target_arch = "x86_64", target_arch = "x86"),
-    target_feature = "avx2",
-    not(miri)
+        #[cfg(not(any(
+            all(
+                any(target_arch = "x86_64", target_arch = "x86"),
+                evil_json_1_89,
+                target_feature = "avx512bw",
+                not(miri)
+            ),
+            all(
+                any(target_arch = "x86_64", target_arch = "x86"),
+                not(evil_json_1_89),
+                target_feature = "avx2",
+                not(miri)
+            )
+        )))]
+        {
+            dispatch::current()
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        evil_json_1_89,
+        target_feature = "avx512bw",
+        not(miri)
+    ),
+    all(
+        any(target_arch = "x86_64", target_arch = "x86"),
+        not(evil_json_1_89),
+        target_feature = "avx2",
+        not(miri)
+    )
 )))]
-pub(crate) use naive::escape;
+mod dispatch {
+    use super::Backend;
+    use std::sync::atomic::{AtomicU8, Ordering};
 
-#[cfg(all(
-    any(target_arch = "x86_64", target_arch = "x86"),
-    target_feature = "avx2",
-    not(miri)
-))]
-pub(crate) use avx2::escape;
+    /// Detected backend, or 0 if the CPU has not been checked yet
+    static CURRENT: AtomicU8 = AtomicU8::new(0);
 
-use crate::bufwrite::BufWrite;
+    #[inline]
+    pub(super) fn current() -> Backend {
+        match CURRENT.load(Ordering::Relaxed) {
+            1 => Backend::Naive,
+            2 => Backend::Swar,
+            #[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+            3 => Backend::PortableSimd,
+            #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+            4 => Backend::Sse2,
+            #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+            5 => Backend::Ssse3,
+            #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+            6 => Backend::Avx2,
+            #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+            7 => Backend::Avx512,
+            _ => detect(),
+        }
+    }
+
+    #[cold]
+    fn detect() -> Backend {
+        let backend = *Backend::available().last().unwrap();
+        CURRENT.store(backend as u8, Ordering::Relaxed);
+        backend
+    }
+}
+
+/// Escapes `feed` with the specified backend
+///
+/// # Safety
+///
+/// `backend` must be supported by the CPU.
+#[inline]
+pub(crate) unsafe fn escape_with<B: BufWrite>(
+    backend: Backend,
+    buf: &mut B,
+    feed: &str,
+) -> io::Result<()> {
+    match backend {
+        Backend::Naive => naive::escape(buf, feed),
+        Backend::Swar => swar::escape(buf, feed),
+        #[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+        Backend::PortableSimd => portable::escape(buf, feed),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Sse2 => sse2::escape(buf, feed),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Ssse3 => sse2::escape(buf, feed),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Avx2 => avx2::escape(buf, feed),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+        Backend::Avx512 => avx512::escape(buf, feed),
+    }
+}
+
+/// Escapes `feed` with `escaper` and the specified backend
+///
+/// `Swar` and `Sse2`, which have no byte shuffles, compare each byte with
+/// every run of consecutive printable ASCII bytes `escaper` escapes.
+///
+/// # Safety
+///
+/// `backend` must be supported by the CPU.
+#[inline]
+pub(crate) unsafe fn escape_table_with<B: BufWrite>(
+    backend: Backend,
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    match backend {
+        Backend::Naive => escaper.escape(buf, feed),
+        Backend::Swar => swar::escape_table(buf, feed, escaper),
+        #[cfg(all(feature = "nightly-simd", evil_json_nightly))]
+        Backend::PortableSimd => portable::escape_table(buf, feed, escaper),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Sse2 => sse2::escape_compare(buf, feed, escaper),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Ssse3 => sse2::escape_table(buf, feed, escaper),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), not(miri)))]
+        Backend::Avx2 => avx2::escape_table(buf, feed, escaper),
+        #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+        Backend::Avx512 => avx512::escape_table(buf, feed, escaper),
+    }
+}
+
+#[inline]
+pub(crate) fn escape<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: Option<&Escaper>,
+) -> io::Result<()> {
+    // SAFETY: `Backend::current` only returns supported backends
+    unsafe {
+        match escaper {
+            None => escape_with(Backend::current(), buf, feed),
+            Some(escaper) => escape_table_with(Backend::current(), buf, feed, escaper),
+        }
+    }
+}
 
 #[cold]
 #[inline(never)]
-pub(crate) fn escape_cold<W: BufWrite>(buf: &mut W, feed: &str) -> std::io::Result<()> {
-    escape(buf, feed)
+pub(crate) fn escape_cold<W: BufWrite>(
+    buf: &mut W,
+    feed: &str,
+    escaper: Option<&Escaper>,
+) -> std::io::Result<()> {
+    escape(buf, feed, escaper)
+}
+
+/// Same as [`need_escape`], but also checks the characters escaped by
+/// `escaper`
+#[cfg_attr(not(debug_assertions), inline(always))]
+pub(crate) fn need_escape_with(feed: &'static str, escaper: Option<&Escaper>) -> bool {
+    match escaper {
+        None => need_escape(feed),
+        Some(escaper) => escaper.need_escape(feed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_table_with, escape_with, naive, Backend, Escaper, ESCAPED};
+    use crate::options::{EscapeProfile, EscapeTable, NonAsciiPolicy, SerializerBuilder};
+
+    fn escape_to_vec(backend: Backend, feed: &str) -> Vec<u8> {
+        let mut buf = Vec::new();
+        unsafe { escape_with(backend, &mut buf, feed).unwrap() };
+        buf
+    }
+
+    #[test]
+    fn current() {
+        assert!(Backend::available().contains(&Backend::current()));
+        assert_eq!(Backend::current(), Backend::current());
+    }
+
+    #[test]
+    #[cfg(all(any(target_arch = "x86_64", target_arch = "x86"), evil_json_1_89, not(miri)))]
+    fn avx512() {
+        // nothing to check on CPUs without AVX-512BW
+        if !Backend::available().contains(&Backend::Avx512) {
+            return;
+        }
+
+        let feed = "\"abc\\\u{1f}\u{3042}".repeat(40);
+        for start in 0..feed.len() {
+            if !feed.is_char_boundary(start) {
+                continue;
+            }
+            let feed = &feed[start..];
+            let mut expected = Vec::new();
+            naive::escape(&mut expected, feed).unwrap();
+            assert_eq!(escape_to_vec(Backend::Avx512, feed), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(all(unix, not(miri)))]
+    fn short_strings() {
+        // one readable page between two unmapped guard pages, so that reading
+        // past either end of a string faults
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let bytes = unsafe {
+            let map = libc::mmap(
+                std::ptr::null_mut(),
+                3 * page,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            assert_ne!(map, libc::MAP_FAILED);
+            let map = map as *mut u8;
+            assert_eq!(libc::mprotect(map as _, page, libc::PROT_NONE), 0);
+            assert_eq!(
+                libc::mprotect(map.add(2 * page) as _, page, libc::PROT_NONE),
+                0
+            );
+            std::slice::from_raw_parts_mut(map.add(page), page)
+        };
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = b"ab\"c\\\x01"[i % 6];
+        }
+
+        for backend in Backend::available() {
+            // strings starting at the first byte and ending at the last byte of
+            // the page
+            for len in 0..=64 {
+                for &start in &[0, page - len] {
+                    let feed = std::str::from_utf8(&bytes[start..start + len]).unwrap();
+                    let mut expected = Vec::new();
+                    naive::escape(&mut expected, feed).unwrap();
+                    assert_eq!(escape_to_vec(backend, feed), expected, "{:?}", backend);
+                }
+            }
+        }
+
+        unsafe {
+            libc::munmap(bytes.as_mut_ptr().sub(page) as _, 3 * page);
+        }
+    }
+
+    #[test]
+    fn backends() {
+        let mut state = 0x9c13a55cd027849au64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for backend in Backend::available() {
+            for len in 0..=200 {
+                for _ in 0..20 {
+                    let feed: String = (0..len)
+                        .map(|_| match next() % 8 {
+                            0 => (next() % 0x20) as u8 as char,
+                            1 => '"',
+                            2 => '\\',
+                            3 => '\u{3042}',
+                            _ => (0x20 + next() % 0x60) as u8 as char,
+                        })
+                        .collect();
+
+                    let mut expected = Vec::new();
+                    naive::escape(&mut expected, &feed).unwrap();
+                    assert_eq!(
+                        escape_to_vec(backend, &feed),
+                        expected,
+                        "backend = {:?}, feed = {:?}",
+                        backend,
+                        feed
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn table_backends() {
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let custom = EscapeTable::new()
+            .escape(b'/', *b"\\/")
+            .escape(b'a', *b"\\u")
+            .escape(0x7F, *b"\\u")
+            .uppercase_hex(true);
+        let large = (b'a'..=b'j').fold(EscapeTable::new(), |t, b| t.escape(b, *b"\\u"));
+        // every other printable byte, which `Swar` and `Sse2` compare one by one
+        let sparse = (0x21..0x80)
+            .step_by(2)
+            .fold(EscapeTable::new(), |t, b| t.escape(b, *b"\\u"));
+        let configs = [
+            (NonAsciiPolicy::Escape, EscapeProfile::Json, None),
+            (NonAsciiPolicy::EscapeNonBmp, EscapeProfile::Json, None),
+            (NonAsciiPolicy::Keep, EscapeProfile::HtmlSafe, None),
+            (NonAsciiPolicy::EscapeNonBmp, EscapeProfile::HtmlSafe, None),
+            (
+                NonAsciiPolicy::Keep,
+                EscapeProfile::Json,
+                Some(custom.clone()),
+            ),
+            (
+                NonAsciiPolicy::Escape,
+                EscapeProfile::HtmlSafe,
+                Some(custom),
+            ),
+            (NonAsciiPolicy::EscapeNonBmp, EscapeProfile::Json, Some(large)),
+            (NonAsciiPolicy::Keep, EscapeProfile::HtmlSafe, Some(sparse)),
+        ];
+
+        for (policy, profile, table) in configs.iter().cloned() {
+            let mut builder = SerializerBuilder::new()
+                .non_ascii(policy)
+                .escape_profile(profile);
+            if let Some(table) = table {
+                builder = builder.escape_table(table);
+            }
+            let options = builder.build();
+            let escaper: &Escaper = options.escaper().unwrap();
+
+            let table = options.escape_table().map_or(ESCAPED, |t| *t.entries());
+            let uppercase = options.escape_table().map_or(false, |t| t.uppercase_hex);
+            let unicode = |c: char| match c {
+                '<' | '>' | '&' | '\'' | '\u{2028}' | '\u{2029}'
+                    if profile == EscapeProfile::HtmlSafe =>
+                {
+                    true
+                }
+                '\u{0}'..='\u{7f}' => table[c as usize] == *b"\\u",
+                '\u{80}'..='\u{ffff}' => policy == NonAsciiPolicy::Escape,
+                _ => policy != NonAsciiPolicy::Keep,
+            };
+
+            for backend in Backend::available() {
+                for len in 0..=150 {
+                    for _ in 0..10 {
+                        let feed: String = (0..len)
+                            .map(|_| match next() % 12 {
+                                0 => (next() % 0x20) as u8 as char,
+                                1 => '"',
+                                2 => '\u{e9}',
+                                3 => '\u{3042}',
+                                4 => '\u{1f600}',
+                                5 => ['<', '/', '\u{7f}'][next() as usize % 3],
+                                6 => char::from_u32(0x2027 + next() as u32 % 3).unwrap(),
+                                _ => (0x20 + next() % 0x60) as u8 as char,
+                            })
+                            .collect();
+
+                        let mut expected = Vec::new();
+                        for c in feed.chars() {
+                            if unicode(c) {
+                                for unit in c.encode_utf16(&mut [0; 2]) {
+                                    let hex = if uppercase {
+                                        format!("\\u{:04X}", unit)
+                                    } else {
+                                        format!("\\u{:04x}", unit)
+                                    };
+                                    expected.extend(hex.bytes());
+                                }
+                            } else if c < '\u{80}' && table[c as usize] != [0, 0] {
+                                expected.extend(&table[c as usize]);
+                            } else {
+                                expected.extend(c.encode_utf8(&mut [0; 4]).bytes());
+                            }
+                        }
+
+                        let mut buf = Vec::new();
+                        unsafe {
+                            escape_table_with(backend, &mut buf, &feed, escaper).unwrap()
+                        };
+                        assert_eq!(
+                            buf, expected,
+                            "backend = {:?}, options = {:?}, feed = {:?}",
+                            backend, options, feed
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compared_bytes() {
+        let escaper = |table: EscapeTable, profile| {
+            let options = SerializerBuilder::new()
+                .escape_table(table)
+                .escape_profile(profile)
+                .build();
+            options.escaper().unwrap().clone()
+        };
+
+        let table = EscapeTable::new()
+            .escape(b'/', *b"\\/")
+            .escape(b'a', *b"\\u")
+            .escape(0x7F, *b"\\u");
+        assert_eq!(
+            escaper(table.clone(), EscapeProfile::HtmlSafe).ranges,
+            [
+                (b'&', b'\''),
+                (b'/', b'/'),
+                (b'<', b'<'),
+                (b'>', b'>'),
+                (b'a', b'a'),
+                (0x7F, 0x7F)
+            ]
+        );
+        assert_eq!(
+            escaper(table, EscapeProfile::Json).ranges,
+            [(b'/', b'/'), (b'a', b'a'), (0x7F, 0x7F)]
+        );
+        assert_eq!(escaper(EscapeTable::new(), EscapeProfile::Json).ranges, []);
+
+        let large = (b'a'..=b'z').fold(EscapeTable::new(), |t, b| t.escape(b, *b"\\u"));
+        assert_eq!(escaper(large, EscapeProfile::Json).ranges, [(b'a', b'z')]);
+
+        // runs may include `"` and `\`
+        let quoted = EscapeTable::new()
+            .escape(b'!', *b"\\u")
+            .escape(b'[', *b"\\u");
+        assert_eq!(
+            escaper(quoted, EscapeProfile::Json).ranges,
+            [(b'!', b'"'), (b'[', b'\\')]
+        );
+    }
 }
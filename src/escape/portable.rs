@@ -0,0 +1,138 @@
+use super::common::{write_masked, write_rest, BS, QU};
+use super::table::Escaper;
+use crate::bufwrite::BufWrite;
+
+use std::io;
+use std::simd::prelude::*;
+
+const LANES: usize = 16;
+
+/// Returns the mask of bytes in `v` which must be escaped
+#[inline]
+fn escape_mask(v: u8x16) -> u64 {
+    let mask = v.simd_lt(u8x16::splat(0x20))
+        | v.simd_eq(u8x16::splat(QU[1]))
+        | v.simd_eq(u8x16::splat(BS[1]));
+    mask.to_bitmask()
+}
+
+/// Escapes a string shorter than `LANES` bytes
+#[inline]
+fn escape_short<B: BufWrite>(buf: &mut B, bytes: &[u8]) -> io::Result<()> {
+    // lanes after the end of `bytes` are never read, and filled with zero
+    let v = u8x16::load_or_default(bytes);
+    let mask = escape_mask(v) & ((1 << bytes.len()) - 1);
+
+    if likely!(mask == 0) {
+        tri!(buf.reserve(LANES));
+        unsafe {
+            std::ptr::write_unaligned(buf.next_ptr() as *mut u8x16, v);
+            buf.advance(bytes.len());
+        }
+        return Ok(());
+    }
+
+    let mut read_ptr = bytes.as_ptr();
+    tri!(unsafe { write_masked(buf, bytes.as_ptr(), mask, &mut read_ptr) });
+
+    let end_ptr = bytes[bytes.len()..].as_ptr();
+    if read_ptr < end_ptr {
+        let part = unsafe {
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize)
+        };
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+    let bytes = feed.as_bytes();
+    if bytes.len() < LANES {
+        return escape_short(buf, bytes);
+    }
+
+    let mut read_ptr = bytes.as_ptr();
+    let mut i = 0;
+
+    while i + LANES <= bytes.len() {
+        let mask = escape_mask(u8x16::from_slice(&bytes[i..]));
+        tri!(unsafe { write_masked(buf, bytes[i..].as_ptr(), mask, &mut read_ptr) });
+        i += LANES;
+    }
+
+    if i < bytes.len() {
+        // the last block overlaps with the previous one, so ignore the bytes
+        // which have already been checked
+        let last = bytes.len() - LANES;
+        let mask = escape_mask(u8x16::from_slice(&bytes[last..])) >> (i - last);
+        tri!(unsafe { write_masked(buf, bytes[i..].as_ptr(), mask, &mut read_ptr) });
+    }
+
+    let end_ptr = bytes[bytes.len()..].as_ptr();
+    if read_ptr < end_ptr {
+        let part = unsafe {
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize)
+        };
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the mask of bytes in `v` which are set in the nibble lookup
+/// tables of an [`Escaper`]
+#[inline]
+fn table_mask(v: u8x16, lut: &[[u8; 16]; 2]) -> u64 {
+    const BITS: u8x16 =
+        u8x16::from_array([1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128]);
+
+    let lo = v & u8x16::splat(0xF);
+    let hi = v >> 4;
+    // bytes from 0x80 use the second table
+    let row = v.simd_ge(u8x16::splat(0x80)).select(
+        u8x16::from_array(lut[1]).swizzle_dyn(lo),
+        u8x16::from_array(lut[0]).swizzle_dyn(lo),
+    );
+    (row & BITS.swizzle_dyn(hi))
+        .simd_ne(u8x16::splat(0))
+        .to_bitmask()
+}
+
+/// Same as [`escape`], but escapes with `escaper`
+pub(crate) fn escape_table<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    let bytes = feed.as_bytes();
+    let mut read_ptr = bytes.as_ptr();
+    let end_ptr = bytes[bytes.len()..].as_ptr();
+
+    if bytes.len() < LANES {
+        let mask = table_mask(u8x16::load_or_default(bytes), &escaper.lut)
+            & ((1 << bytes.len()) - 1);
+        tri!(unsafe { escaper.write_masked(buf, bytes.as_ptr(), mask, &mut read_ptr) });
+        return unsafe { write_rest(buf, read_ptr, end_ptr) };
+    }
+
+    let mut i = 0;
+    while i + LANES <= bytes.len() {
+        let mask = table_mask(u8x16::from_slice(&bytes[i..]), &escaper.lut);
+        tri!(unsafe {
+            escaper.write_masked(buf, bytes[i..].as_ptr(), mask, &mut read_ptr)
+        });
+        i += LANES;
+    }
+
+    if i < bytes.len() {
+        let last = bytes.len() - LANES;
+        let mask =
+            table_mask(u8x16::from_slice(&bytes[last..]), &escaper.lut) >> (i - last);
+        tri!(unsafe {
+            escaper.write_masked(buf, bytes[i..].as_ptr(), mask, &mut read_ptr)
+        });
+    }
+
+    unsafe { write_rest(buf, read_ptr, end_ptr) }
+}
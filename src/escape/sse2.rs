@@ -0,0 +1,248 @@
+use super::common::{write_masked, write_rest, BS, QU};
+use super::table::Escaper;
+use crate::bufwrite::BufWrite;
+
+use std::io;
+
+/// The smallest page size on x86 processors
+const PAGE_SIZE: usize = 4096;
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Returns the mask of bytes in `v` which must be escaped
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn escape_mask(v: __m128i) -> u64 {
+    let v_bound = _mm_set1_epi8(0x1f);
+    let v_quot = _mm_set1_epi8(QU[1] as i8);
+    let v_bs = _mm_set1_epi8(BS[1] as i8);
+
+    _mm_movemask_epi8(_mm_or_si128(
+        _mm_cmpeq_epi8(_mm_min_epu8(v, v_bound), v),
+        _mm_or_si128(_mm_cmpeq_epi8(v, v_quot), _mm_cmpeq_epi8(v, v_bs)),
+    )) as u32 as u64
+}
+
+/// Loads up to 16 bytes from `ptr` without crossing a page boundary
+///
+/// Bytes after `len` are unspecified.
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn load_short(ptr: *const u8, len: usize) -> __m128i {
+    debug_assert!(len < 16);
+
+    if likely!((ptr as usize & (PAGE_SIZE - 1)) <= PAGE_SIZE - 16) {
+        // reading beyond `len` never faults since the bytes are on the same
+        // page as `ptr`
+        _mm_loadu_si128(ptr as _)
+    } else {
+        let mut tmp = [0u8; 16];
+        std::ptr::copy_nonoverlapping(ptr, tmp.as_mut_ptr(), len);
+        _mm_loadu_si128(tmp.as_ptr() as _)
+    }
+}
+
+/// Escapes a string shorter than 16 bytes
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn escape_short<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+    if feed.is_empty() {
+        // `feed.as_ptr()` may be dangling
+        return Ok(());
+    }
+
+    let ptr = feed.as_ptr();
+    let len = feed.len();
+    let v = load_short(ptr, len);
+    let mask = escape_mask(v) & ((1 << len) - 1);
+
+    if likely!(mask == 0) {
+        tri!(buf.reserve(16));
+        _mm_storeu_si128(buf.next_ptr() as _, v);
+        buf.advance(len);
+        return Ok(());
+    }
+
+    let mut read_ptr = ptr;
+    let end_ptr = ptr.add(len);
+    tri!(write_masked(buf, ptr, mask, &mut read_ptr));
+
+    if read_ptr < end_ptr {
+        let part =
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize);
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
+
+/// # Safety
+///
+/// The CPU must support SSE2.
+#[target_feature(enable = "sse2")]
+pub(crate) unsafe fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+    if feed.len() < 16 {
+        return escape_short(buf, feed);
+    }
+
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    while end_ptr as usize - ptr as usize >= 16 {
+        let mask = escape_mask(_mm_loadu_si128(ptr as _));
+        tri!(write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(16);
+    }
+
+    if ptr != end_ptr {
+        // the last block overlaps with the previous one, so ignore the bytes
+        // which have already been checked
+        let last = end_ptr.sub(16);
+        let mask = escape_mask(_mm_loadu_si128(last as _)) >> (ptr as usize - last as usize);
+        tri!(write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    if read_ptr < end_ptr {
+        let part =
+            std::slice::from_raw_parts(read_ptr, end_ptr as usize - read_ptr as usize);
+        buf.write_all(part)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the mask of bytes in `v` which may be escaped by `escaper`
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn compare_mask(v: __m128i, escaper: &Escaper) -> u64 {
+    let v_lead = _mm_set1_epi8(escaper.min_lead as i8);
+    let mut hit = _mm_cmpeq_epi8(_mm_max_epu8(v, v_lead), v);
+    if escaper.line_separators {
+        hit = _mm_or_si128(hit, _mm_cmpeq_epi8(v, _mm_set1_epi8(0xE2u8 as i8)));
+    }
+    for &(lo, hi) in &escaper.ranges {
+        // `lo <= v <= hi` iff clamping `v` to the range leaves it unchanged
+        let v_lo = _mm_set1_epi8(lo as i8);
+        let v_hi = _mm_set1_epi8(hi as i8);
+        let clamped = _mm_min_epu8(_mm_max_epu8(v, v_lo), v_hi);
+        hit = _mm_or_si128(hit, _mm_cmpeq_epi8(clamped, v));
+    }
+
+    escape_mask(v) | _mm_movemask_epi8(hit) as u32 as u64
+}
+
+/// Same as [`escape`], but escapes with `escaper` by comparing each byte
+/// with its `ranges`
+///
+/// # Safety
+///
+/// The CPU must support SSE2.
+#[target_feature(enable = "sse2")]
+pub(crate) unsafe fn escape_compare<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    if feed.len() < 16 {
+        if feed.is_empty() {
+            // `feed.as_ptr()` may be dangling
+            return Ok(());
+        }
+
+        let mask =
+            compare_mask(load_short(ptr, feed.len()), escaper) & ((1 << feed.len()) - 1);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        return write_rest(buf, read_ptr, end_ptr);
+    }
+
+    while end_ptr as usize - ptr as usize >= 16 {
+        let mask = compare_mask(_mm_loadu_si128(ptr as _), escaper);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(16);
+    }
+
+    if ptr != end_ptr {
+        let last = end_ptr.sub(16);
+        let mask = compare_mask(_mm_loadu_si128(last as _), escaper)
+            >> (ptr as usize - last as usize);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    write_rest(buf, read_ptr, end_ptr)
+}
+
+/// Returns the mask of bytes in `v` which are set in the nibble lookup
+/// tables of an [`Escaper`]
+#[inline]
+#[target_feature(enable = "ssse3")]
+pub(super) unsafe fn table_mask(v: __m128i, lut: &[[u8; 16]; 2]) -> u64 {
+    let v_lut_lo = _mm_loadu_si128(lut[0].as_ptr() as _);
+    let v_lut_hi = _mm_loadu_si128(lut[1].as_ptr() as _);
+    let v_bits =
+        _mm_setr_epi8(1, 2, 4, 8, 16, 32, 64, -128, 1, 2, 4, 8, 16, 32, 64, -128);
+    let v_nibble = _mm_set1_epi8(0xF);
+    let v_zero = _mm_setzero_si128();
+
+    let lo = _mm_and_si128(v, v_nibble);
+    let hi = _mm_and_si128(_mm_srli_epi16(v, 4), v_nibble);
+    // bytes from 0x80 use the second table
+    let high = _mm_cmplt_epi8(v, v_zero);
+    let row = _mm_or_si128(
+        _mm_andnot_si128(high, _mm_shuffle_epi8(v_lut_lo, lo)),
+        _mm_and_si128(high, _mm_shuffle_epi8(v_lut_hi, lo)),
+    );
+    let hit = _mm_and_si128(row, _mm_shuffle_epi8(v_bits, hi));
+
+    !_mm_movemask_epi8(_mm_cmpeq_epi8(hit, v_zero)) as u32 as u64 & 0xFFFF
+}
+
+/// Same as [`escape`], but escapes with `escaper`
+///
+/// # Safety
+///
+/// The CPU must support SSSE3.
+#[target_feature(enable = "ssse3")]
+pub(crate) unsafe fn escape_table<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    let mut ptr = feed.as_ptr();
+    let mut read_ptr = ptr;
+    let end_ptr = feed[feed.len()..].as_ptr();
+
+    if feed.len() < 16 {
+        if feed.is_empty() {
+            // `feed.as_ptr()` may be dangling
+            return Ok(());
+        }
+
+        let mask = table_mask(load_short(ptr, feed.len()), &escaper.lut)
+            & ((1 << feed.len()) - 1);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        return write_rest(buf, read_ptr, end_ptr);
+    }
+
+    while end_ptr as usize - ptr as usize >= 16 {
+        let mask = table_mask(_mm_loadu_si128(ptr as _), &escaper.lut);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+        ptr = ptr.add(16);
+    }
+
+    if ptr != end_ptr {
+        let last = end_ptr.sub(16);
+        let mask = table_mask(_mm_loadu_si128(last as _), &escaper.lut)
+            >> (ptr as usize - last as usize);
+        tri!(escaper.write_masked(buf, ptr, mask, &mut read_ptr));
+    }
+
+    write_rest(buf, read_ptr, end_ptr)
+}
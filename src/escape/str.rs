@@ -0,0 +1,103 @@
+use super::{escape, escape_table_with, escape_with, Backend, Escaper};
+use crate::bufwrite::{BufWrite, PtrWriter};
+use crate::raw::{RawStr, SerializeRaw};
+
+use core::cmp;
+use std::io;
+
+/// Bytes which backends may write at once when escaping short strings
+const SHORT_STORE: usize = 16;
+
+/// Escaped contents of a string, without quotes
+pub(crate) struct EscapedStr<'a>(pub &'a str, pub Option<&'a Escaper>);
+
+/// Escaped string enclosed in double quotes
+pub(crate) struct QuotedStr<'a>(pub &'a str, pub Option<&'a Escaper>);
+
+impl<'a> SerializeRaw for EscapedStr<'a> {
+    #[inline]
+    fn size_hint(&self) -> usize {
+        // every byte expands to `\u00XX` in the worst case, and a 4-byte
+        // character to a 12-byte surrogate pair. SIMD backends may also store
+        // 16 bytes at once for short strings.
+        cmp::max(self.0.len().saturating_mul(6), SHORT_STORE)
+    }
+
+    #[inline]
+    unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
+        let mut writer = PtrWriter::new(dst, self.size_hint());
+        // writing to `PtrWriter` never fails
+        let _ = match self.1 {
+            None => escape_with(Backend::current(), &mut writer, self.0),
+            Some(escaper) => {
+                escape_table_with(Backend::current(), &mut writer, self.0, escaper)
+            }
+        };
+        writer.as_ptr() as usize - dst as usize
+    }
+}
+
+impl<'a> SerializeRaw for QuotedStr<'a> {
+    #[inline]
+    fn size_hint(&self) -> usize {
+        EscapedStr(self.0, self.1).size_hint().saturating_add(2)
+    }
+
+    #[inline]
+    unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
+        *dst = b'"';
+        let len = EscapedStr(self.0, self.1).write_to_ptr(dst.add(1));
+        *dst.add(len + 1) = b'"';
+        len + 2
+    }
+}
+
+/// Writes `feed` enclosed in double quotes, followed by `suffix`
+///
+/// The worst-case length is reserved at once unless it exceeds
+/// `BufWrite::max_reserve`.
+#[inline]
+pub(crate) fn write_quoted<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    suffix: &'static str,
+    escaper: Option<&Escaper>,
+) -> io::Result<()> {
+    let value = QuotedStr(feed, escaper);
+    if likely!(value.size_hint().saturating_add(suffix.len()) <= buf.max_reserve()) {
+        buf.write2(&value, &RawStr(suffix))
+    } else {
+        write_chunked(buf, "\"", feed, suffix, escaper)
+    }
+}
+
+/// Writes escaped `feed` without quotes, followed by `suffix`
+#[inline]
+pub(crate) fn write_escaped<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    suffix: &'static str,
+    escaper: Option<&Escaper>,
+) -> io::Result<()> {
+    let value = EscapedStr(feed, escaper);
+    if likely!(value.size_hint().saturating_add(suffix.len()) <= buf.max_reserve()) {
+        buf.write2(&value, &RawStr(suffix))
+    } else {
+        write_chunked(buf, "", feed, suffix, escaper)
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn write_chunked<B: BufWrite>(
+    buf: &mut B,
+    quote: &'static str,
+    feed: &str,
+    suffix: &'static str,
+    escaper: Option<&Escaper>,
+) -> io::Result<()> {
+    tri!(buf.write_all(quote.as_bytes()));
+    tri!(escape(buf, feed, escaper));
+    tri!(buf.write_all(quote.as_bytes()));
+    buf.write_all(suffix.as_bytes())
+}
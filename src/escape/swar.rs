@@ -0,0 +1,168 @@
+use super::common::{u_encode, write_rest, ESCAPED, UU};
+use super::table::Escaper;
+use crate::bufwrite::BufWrite;
+
+use core::ptr;
+use std::io;
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+/// Returns true if any byte in `x` is zero
+#[inline]
+fn has_zero(x: u64) -> bool {
+    x.wrapping_sub(LO) & !x & HI != 0
+}
+
+/// Returns true if any byte in `word` must be escaped
+///
+/// This function checks control characters, `"` and `\` in eight bytes at once.
+#[inline]
+fn need_escape(word: u64) -> bool {
+    // for bytes less than 0x20, subtracting 0x20 borrows from the high bit
+    let control = word.wrapping_sub(LO * 0x20) & !word & HI != 0;
+    control || has_zero(word ^ (LO * b'"' as u64)) || has_zero(word ^ (LO * b'\\' as u64))
+}
+
+/// Returns true if any byte in `word` may be escaped by `escaper`
+#[inline]
+fn need_escape_table(word: u64, escaper: &Escaper) -> bool {
+    // `(byte & 0x7F) + (0x80 - (min_lead & 0x7F))` sets the high bit of the
+    // bytes from `0x80` which are at least `min_lead`
+    let ascii = word & !HI;
+    let lead = LO * (0x80 - (escaper.min_lead & 0x7F)) as u64;
+    let non_ascii = (ascii + lead) & word & HI != 0;
+    need_escape(word)
+        || non_ascii
+        || (escaper.line_separators && has_zero(word ^ (LO * 0xE2)))
+        || escaper.ranges.iter().any(|&(lo, hi)| {
+            // adding `0x80 - lo` sets the high bit of ASCII bytes from `lo`,
+            // and adding `0x7F - hi` that of bytes above `hi`
+            let from_lo = ascii + LO * (0x80 - lo) as u64;
+            let above_hi = ascii + LO * (0x7F - hi) as u64;
+            from_lo & !above_hi & !word & HI != 0
+        })
+}
+
+#[inline]
+pub(crate) fn escape<B: BufWrite>(buf: &mut B, feed: &str) -> io::Result<()> {
+    let bytes = feed.as_bytes();
+
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // skip eight bytes at once while no byte needs escape
+        if i + 8 <= bytes.len() {
+            let word = unsafe { ptr::read_unaligned(bytes.as_ptr().add(i) as *const u64) };
+            if !need_escape(word) {
+                i += 8;
+                continue;
+            }
+        }
+
+        let end = if i + 8 <= bytes.len() { i + 8 } else { bytes.len() };
+        while i < end {
+            let escape = ESCAPED[bytes[i] as usize];
+            if escape != [0, 0] {
+                if i > start {
+                    tri!(buf.write_all(&bytes[start..i]));
+                }
+
+                if escape != UU {
+                    tri!(buf.write_all(&escape));
+                } else {
+                    tri!(u_encode(buf, bytes[i]));
+                }
+
+                start = i + 1;
+            }
+            i += 1;
+        }
+    }
+
+    if start < bytes.len() {
+        buf.write_all(&bytes[start..])
+    } else {
+        Ok(())
+    }
+}
+
+/// Same as [`escape`], but escapes with `escaper`
+///
+/// Each word is compared with every run in `escaper.ranges`.
+#[inline]
+pub(crate) fn escape_table<B: BufWrite>(
+    buf: &mut B,
+    feed: &str,
+    escaper: &Escaper,
+) -> io::Result<()> {
+    let bytes = feed.as_bytes();
+    let mut read_ptr = bytes.as_ptr();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // skip eight bytes at once while no byte needs escape
+        if i + 8 <= bytes.len() {
+            let word = unsafe { ptr::read_unaligned(bytes.as_ptr().add(i) as *const u64) };
+            if !need_escape_table(word, escaper) {
+                i += 8;
+                continue;
+            }
+        }
+
+        let end = if i + 8 <= bytes.len() { i + 8 } else { bytes.len() };
+        while i < end {
+            if escaper.may_escape(bytes[i]) {
+                tri!(unsafe { escaper.write_at(buf, bytes[i..].as_ptr(), &mut read_ptr) });
+            }
+            i += 1;
+        }
+    }
+
+    unsafe { write_rest(buf, read_ptr, bytes[bytes.len()..].as_ptr()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{need_escape, need_escape_table};
+    use crate::{EscapeTable, SerializerBuilder};
+
+    #[test]
+    fn word() {
+        for byte in 0..=255u8 {
+            let expected = byte < 0x20 || byte == b'"' || byte == b'\\';
+            for pos in 0..8 {
+                let mut bytes = *b"abcdefgh";
+                bytes[pos] = byte;
+                assert_eq!(need_escape(u64::from_ne_bytes(bytes)), expected, "{}", byte);
+
+                // neighbouring bytes must not affect the result
+                let mut bytes = [0xff; 8];
+                bytes[pos] = byte;
+                assert_eq!(need_escape(u64::from_ne_bytes(bytes)), expected, "{}", byte);
+            }
+        }
+    }
+    #[test]
+    fn word_table() {
+        let table = (b'a'..=b'z')
+            .chain(vec![b'!', b'/', b'~', 0x7F])
+            .fold(EscapeTable::new(), |t, b| t.escape(b, *b"\\u"));
+        let options = SerializerBuilder::new().escape_table(table).build();
+        let escaper = options.escaper().unwrap();
+
+        for byte in 0..=255u8 {
+            // `0xFF` never occurs in UTF-8, so it is checked with lead bytes
+            let expected = escaper.may_escape(byte) || byte == 0xFF;
+            for pos in 0..8 {
+                for &fill in &[b' ', b'`', b'{', 0x80] {
+                    let mut bytes = [fill; 8];
+                    bytes[pos] = byte;
+                    let word = u64::from_ne_bytes(bytes);
+                    assert_eq!(need_escape_table(word, escaper), expected, "{}", byte);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Escaping driven by a per-byte table, used when [`Options`] changes the
+//! standard JSON escapes
+
+use super::common::{write_rest, ESCAPED, UU};
+use crate::bufwrite::BufWrite;
+use crate::options::{EscapeProfile, NonAsciiPolicy, Options};
+
+use core::fmt;
+use std::io;
+
+const __: [u8; 2] = [0, 0];
+
+/// Table entry for the lead byte of a character which may be escaped,
+/// depending on its code point
+const CP: [u8; 2] = [0, 1];
+
+static HEX_DIGITS: [u8; 16] = *b"0123456789abcdef";
+static HEX_DIGITS_UPPER: [u8; 16] = *b"0123456789ABCDEF";
+
+#[derive(Clone)]
+pub(crate) struct Escaper {
+    /// Escape sequence of each byte, `UU` for `\u00XX` or `CP` for the lead
+    /// byte of a character which may be escaped
+    table: [[u8; 2]; 256],
+    /// Nibble lookup tables of the bytes which are not `__` in `table`, for
+    /// SIMD backends
+    ///
+    /// Bit `hi` of `lut[0][lo]` is set if the byte `hi << 4 | lo` must be
+    /// checked, and `lut[1]` is the same for bytes from `0x80`.
+    #[allow(dead_code)]
+    pub(super) lut: [[u8; 16]; 2],
+    /// Runs of consecutive printable ASCII bytes which must be checked, as
+    /// inclusive bounds, for backends without byte shuffles
+    ///
+    /// Runs of only `"` or `\` are left out since those bytes are always
+    /// checked.
+    pub(super) ranges: Vec<(u8, u8)>,
+    /// Lead bytes from this one must be checked, `0xFF` which is never in
+    /// UTF-8 if none are
+    pub(super) min_lead: u8,
+    non_ascii: NonAsciiPolicy,
+    /// Whether U+2028 and U+2029 are escaped
+    pub(super) line_separators: bool,
+    hex_digits: &'static [u8; 16],
+}
+
+impl fmt::Debug for Escaper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Escaper")
+            .field("non_ascii", &self.non_ascii)
+            .field("line_separators", &self.line_separators)
+            .finish()
+    }
+}
+
+impl Escaper {
+    /// Returns `None` if `options` only needs the standard JSON escapes
+    pub(crate) fn new(options: &Options) -> Option<Escaper> {
+        if options.non_ascii == NonAsciiPolicy::Keep
+            && options.escape_profile == EscapeProfile::Json
+            && options.escape_table.is_none()
+        {
+            return None;
+        }
+
+        let (mut table, hex_digits) = match options.escape_table {
+            Some(ref custom) if custom.uppercase_hex => {
+                (custom.entries, &HEX_DIGITS_UPPER)
+            }
+            Some(ref custom) => (custom.entries, &HEX_DIGITS),
+            None => (ESCAPED, &HEX_DIGITS),
+        };
+        match options.non_ascii {
+            NonAsciiPolicy::Keep => {}
+            NonAsciiPolicy::Escape => table[0xC0..].iter_mut().for_each(|e| *e = CP),
+            NonAsciiPolicy::EscapeNonBmp => {
+                table[0xF0..].iter_mut().for_each(|e| *e = CP)
+            }
+        }
+
+        let line_separators = options.escape_profile == EscapeProfile::HtmlSafe;
+        if line_separators {
+            for &byte in b"<>&'" {
+                table[byte as usize] = UU;
+            }
+            // lead byte of U+2028 and U+2029
+            table[0xE2] = CP;
+        }
+
+        let mut lut = [[0u8; 16]; 2];
+        for (byte, entry) in table.iter().enumerate() {
+            if *entry != __ {
+                lut[byte >> 7][byte & 0xF] |= 1 << (byte >> 4 & 0x7);
+            }
+        }
+
+        let mut ranges: Vec<(u8, u8)> = Vec::new();
+        for byte in 0x20..0x80u8 {
+            if table[byte as usize] == __ {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.1 + 1 == byte => last.1 = byte,
+                _ => ranges.push((byte, byte)),
+            }
+        }
+        ranges.retain(|&(lo, hi)| !(lo == hi && (lo == b'"' || lo == b'\\')));
+        let min_lead = match options.non_ascii {
+            NonAsciiPolicy::Keep => 0xFF,
+            NonAsciiPolicy::Escape => 0xC0,
+            NonAsciiPolicy::EscapeNonBmp => 0xF0,
+        };
+
+        Some(Escaper {
+            table,
+            lut,
+            ranges,
+            min_lead,
+            non_ascii: options.non_ascii,
+            line_separators,
+            hex_digits,
+        })
+    }
+
+    #[inline]
+    fn escapes_code_point(&self, code: u32) -> bool {
+        let non_ascii = match self.non_ascii {
+            NonAsciiPolicy::Keep => false,
+            NonAsciiPolicy::Escape => true,
+            NonAsciiPolicy::EscapeNonBmp => code >= 0x10000,
+        };
+        non_ascii || (self.line_separators && (code == 0x2028 || code == 0x2029))
+    }
+
+    /// Returns whether `feed` may need to be escaped
+    pub(crate) fn need_escape(&self, feed: &str) -> bool {
+        feed.bytes().any(|byte| self.table[byte as usize] != __)
+    }
+
+    /// Returns whether `byte` must be passed to [`write_at`](Self::write_at)
+    #[inline]
+    pub(super) fn may_escape(&self, byte: u8) -> bool {
+        self.table[byte as usize] != __
+    }
+
+    /// Escapes `feed` without SIMD instructions
+    pub(crate) fn escape<B: BufWrite>(&self, buf: &mut B, feed: &str) -> io::Result<()> {
+        let bytes = feed.as_bytes();
+        let mut read_ptr = bytes.as_ptr();
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            if self.table[byte as usize] != __ {
+                tri!(unsafe { self.write_at(buf, bytes[i..].as_ptr(), &mut read_ptr) });
+            }
+        }
+
+        unsafe { write_rest(buf, read_ptr, bytes[bytes.len()..].as_ptr()) }
+    }
+
+    /// Same as [`write_masked`](super::common::write_masked), but escapes
+    /// the bytes with this table
+    #[allow(dead_code)]
+    #[inline]
+    pub(super) unsafe fn write_masked<B: BufWrite>(
+        &self,
+        buf: &mut B,
+        ptr: *const u8,
+        mut mask: u64,
+        read_ptr: &mut *const u8,
+    ) -> io::Result<()> {
+        while mask != 0 {
+            let trailing_zeros = mask.trailing_zeros() as usize;
+            mask &= mask - 1;
+            tri!(self.write_at(buf, ptr.add(trailing_zeros), read_ptr));
+        }
+
+        Ok(())
+    }
+
+    /// Writes `[*read_ptr, ptr)` and the escaped character at `ptr` if it
+    /// must be escaped
+    ///
+    /// The byte at `ptr` must not be `__` in the table.
+    #[inline]
+    pub(super) unsafe fn write_at<B: BufWrite>(
+        &self,
+        buf: &mut B,
+        ptr: *const u8,
+        read_ptr: &mut *const u8,
+    ) -> io::Result<()> {
+        let key = self.table[*ptr as usize];
+        let (len, code) = if key == CP {
+            let (len, code) = decode(ptr);
+            if !self.escapes_code_point(code) {
+                return Ok(());
+            }
+            (len, code)
+        } else {
+            (1, *ptr as u32)
+        };
+
+        tri!(write_rest(buf, *read_ptr, ptr));
+        *read_ptr = ptr.add(len);
+
+        if key == CP || key == UU {
+            tri!(buf.reserve(12));
+            let len = write_unicode(buf.next_ptr(), code, self.hex_digits);
+            buf.advance(len);
+            Ok(())
+        } else {
+            buf.write_all(&key)
+        }
+    }
+
+    /// Writes the escaped `c` to `dst` and returns the length, which is at
+    /// most 12 bytes
+    #[inline]
+    pub(crate) unsafe fn escape_char_to_ptr(&self, dst: *mut u8, c: char) -> usize {
+        let code = c as u32;
+        if code < 0x80 {
+            let key = self.table[code as usize];
+            if key == __ {
+                *dst = code as u8;
+                1
+            } else if key == UU {
+                write_unicode(dst, code, self.hex_digits)
+            } else {
+                *dst = key[0];
+                *dst.add(1) = key[1];
+                2
+            }
+        } else if self.escapes_code_point(code) {
+            write_unicode(dst, code, self.hex_digits)
+        } else {
+            c.encode_utf8(std::slice::from_raw_parts_mut(dst, 4)).len()
+        }
+    }
+}
+
+/// Decodes the character starting at `ptr`, which must be the lead byte of a
+/// multi-byte UTF-8 sequence
+#[inline]
+unsafe fn decode(ptr: *const u8) -> (usize, u32) {
+    let cont = |i: usize| (*ptr.add(i) & 0x3F) as u32;
+    let lead = *ptr as u32;
+    if lead < 0xE0 {
+        (2, (lead & 0x1F) << 6 | cont(1))
+    } else if lead < 0xF0 {
+        (3, (lead & 0x0F) << 12 | cont(1) << 6 | cont(2))
+    } else {
+        (
+            4,
+            (lead & 0x07) << 18 | cont(1) << 12 | cont(2) << 6 | cont(3),
+        )
+    }
+}
+
+/// Writes `\uXXXX`, or a surrogate pair for `code` above `0xFFFF`, and
+/// returns the length
+#[inline]
+unsafe fn write_unicode(dst: *mut u8, code: u32, hex_digits: &[u8; 16]) -> usize {
+    let write_unit = |dst: *mut u8, unit: u32| {
+        *dst = b'\\';
+        *dst.add(1) = b'u';
+        for i in 0..4 {
+            *dst.add(2 + i) = hex_digits[(unit >> (12 - 4 * i) & 0xF) as usize];
+        }
+    };
+
+    if code < 0x10000 {
+        write_unit(dst, code);
+        6
+    } else {
+        let code = code - 0x10000;
+        write_unit(dst, 0xD800 | code >> 10);
+        write_unit(dst.add(6), 0xDC00 | (code & 0x3FF));
+        12
+    }
+}
@@ -0,0 +1,578 @@
+//! Shortest round-trip formatting of floats
+//!
+//! The digits are computed with Dragonbox (J. Jeon, "Dragonbox: A New
+//! Floating-Point Binary-to-Decimal Conversion Algorithm", 2020) and laid out
+//! the same way as ryu, e.g. `1.0`, `0.001234` and `1.234e33`.
+
+use super::tables::{POW10_32, POW10_32_MIN, POW10_64, POW10_64_MIN};
+
+use core::ptr::{self, copy_nonoverlapping};
+use itoap::Integer;
+
+pub(super) static POW10: [u64; 18] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+    10_000_000_000,
+    100_000_000_000,
+    1_000_000_000_000,
+    10_000_000_000_000,
+    100_000_000_000_000,
+    1_000_000_000_000_000,
+    10_000_000_000_000_000,
+    100_000_000_000_000_000,
+];
+
+/// Writes the shortest representation of a finite `value` and returns the
+/// length, which is at most 16 bytes
+#[inline]
+pub(crate) unsafe fn format32(value: f32, dst: *mut u8) -> usize {
+    debug_assert!(value.is_finite());
+    let bits = value.to_bits();
+    let sign = (bits >> 31) as usize;
+    *dst = b'-';
+    let dst = dst.add(sign);
+
+    let bits = bits & 0x7FFF_FFFF;
+    if bits == 0 {
+        copy_nonoverlapping(b"0.0".as_ptr(), dst, 3);
+        return sign + 3;
+    }
+
+    let (significand, exponent) = to_decimal32(bits);
+    sign + write_decimal(significand as u64, exponent, 13, -6, dst)
+}
+
+/// Writes the shortest representation of a finite `value` and returns the
+/// length, which is at most 24 bytes
+#[inline]
+pub(crate) unsafe fn format64(value: f64, dst: *mut u8) -> usize {
+    debug_assert!(value.is_finite());
+    let bits = value.to_bits();
+    let sign = (bits >> 63) as usize;
+    *dst = b'-';
+    let dst = dst.add(sign);
+
+    let bits = bits & 0x7FFF_FFFF_FFFF_FFFF;
+    if bits == 0 {
+        copy_nonoverlapping(b"0.0".as_ptr(), dst, 3);
+        return sign + 3;
+    }
+
+    let (significand, exponent) = to_decimal64(bits);
+    sign + write_decimal(significand, exponent, 16, -5, dst)
+}
+
+/// `floor(log2(10^e))` for `|e| <= 1233`
+#[inline]
+fn floor_log2_pow10(e: i32) -> i32 {
+    (e * 1_741_647) >> 19
+}
+
+/// `floor(log10(2^e))` for `|e| <= 1500`
+#[inline]
+fn floor_log10_pow2(e: i32) -> i32 {
+    (e * 1_262_611) >> 22
+}
+
+/// `floor(log10(3/4 * 2^e))` for `|e| <= 1500`
+#[inline]
+fn floor_log10_three_quarters_pow2(e: i32) -> i32 {
+    (e * 1_262_611 - 524_031) >> 22
+}
+
+/// Upper 64 bits of a cached power of ten
+#[inline]
+fn high32(cache: u64) -> u64 {
+    cache
+}
+
+#[inline]
+fn high64(cache: u128) -> u64 {
+    (cache >> 64) as u64
+}
+
+/// Integer part of `u * cache`, scaled so that the cache is in `[1/2, 1)`,
+/// and whether the fractional part is zero
+#[inline]
+fn mul32(u: u32, cache: u64) -> (u32, bool) {
+    let r = ((u as u128 * cache as u128) >> 32) as u64;
+    ((r >> 32) as u32, r as u32 == 0)
+}
+
+#[inline]
+fn mul64(u: u64, cache: u128) -> (u64, bool) {
+    let r = u as u128 * (cache >> 64) + ((u as u128 * (cache as u64 as u128)) >> 64);
+    ((r >> 64) as u64, r as u64 == 0)
+}
+
+/// Parity of the integer part of `two_f * cache * 2^beta` and whether the
+/// fractional part is zero
+#[inline]
+fn mul_parity32(two_f: u32, cache: u64, beta: i32) -> (bool, bool) {
+    let r = (two_f as u64).wrapping_mul(cache);
+    ((r >> (64 - beta)) & 1 != 0, (r >> (32 - beta)) as u32 == 0)
+}
+
+#[inline]
+fn mul_parity64(two_f: u64, cache: u128, beta: i32) -> (bool, bool) {
+    let low = two_f as u128 * (cache as u64 as u128);
+    let high = two_f
+        .wrapping_mul((cache >> 64) as u64)
+        .wrapping_add((low >> 64) as u64);
+    let low = low as u64;
+    (
+        (high >> (64 - beta)) & 1 != 0,
+        (high << beta) | (low >> (64 - beta)) == 0,
+    )
+}
+
+macro_rules! impl_to_decimal {
+    (
+        $name:ident, $shorter:ident, $uint:ty,
+        $table:ident, $table_min:ident,
+        $high:ident, $mul:ident, $mul_parity:ident,
+        significand_bits: $sbits:expr,
+        exponent_bias: $bias:expr,
+        kappa: $kappa:expr,
+        divisors: $big_divisor:expr, $small_divisor:expr,
+        tie_exponent: $tie:expr
+    ) => {
+        /// Returns the shortest `(significand, exponent)` which reads back as
+        /// the positive float with `bits`
+        #[inline]
+        pub(super) fn $name(bits: $uint) -> ($uint, i32) {
+            // `10^(kappa + 1)` and `10^kappa`
+            const BIG_DIVISOR: u32 = $big_divisor;
+            const SMALL_DIVISOR: u32 = $small_divisor;
+
+            let ieee_significand = bits & ((1 << $sbits) - 1);
+            let ieee_exponent = (bits >> $sbits) as i32;
+
+            let (two_fc, e) = if ieee_exponent != 0 {
+                let c = 1 << $sbits | ieee_significand;
+                let e = ieee_exponent - $bias - $sbits;
+                // small integers are exact
+                if (-$sbits..=0).contains(&e) && c.trailing_zeros() >= -e as u32 {
+                    return (c >> -e, 0);
+                }
+                if ieee_significand == 0 {
+                    return $shorter(e);
+                }
+                (c << 1, e)
+            } else {
+                (ieee_significand << 1, 1 - $bias - $sbits)
+            };
+
+            // the rounding interval is closed if the significand is even
+            let closed = two_fc & 2 == 0;
+
+            let minus_k = floor_log10_pow2(e) - $kappa;
+            let cache = $table[(-minus_k - $table_min) as usize];
+            let beta = e + floor_log2_pow10(-minus_k);
+            debug_assert!((1..=9).contains(&beta));
+
+            // `deltai` is the width of the interval and `zi` its upper bound,
+            // both scaled by `10^-minus_k`
+            let deltai = ($high(cache) >> (63 - beta)) as u32;
+            let (zi, z_integer) = $mul((two_fc | 1) << beta, cache);
+
+            // try to drop `kappa + 1` digits
+            let mut significand = zi / BIG_DIVISOR as $uint;
+            let mut r = (zi - BIG_DIVISOR as $uint * significand) as u32;
+
+            let small_divisor_case = if r < deltai {
+                // exclude the upper bound of an open interval
+                if r == 0 && z_integer && !closed {
+                    significand -= 1;
+                    r = BIG_DIVISOR;
+                    true
+                } else {
+                    false
+                }
+            } else if r > deltai {
+                true
+            } else {
+                // compare the fractional parts of the bounds
+                let (x_parity, x_integer) = $mul_parity(two_fc - 1, cache, beta);
+                !(x_parity || (x_integer && closed))
+            };
+
+            if !small_divisor_case {
+                return (significand, minus_k + $kappa + 1);
+            }
+
+            // find the closest of the numbers with one more digit
+            significand *= 10;
+            let mut dist = r - deltai / 2 + SMALL_DIVISOR / 2;
+            let approx_y_parity = (dist ^ (SMALL_DIVISOR / 2)) & 1 != 0;
+            let divisible = dist % SMALL_DIVISOR == 0;
+            dist /= SMALL_DIVISOR;
+            significand += dist as $uint;
+
+            if divisible {
+                // the value is either `significand` or `significand - 1`, which
+                // can be told by the parity, and a tie rounds to even
+                let (y_parity, y_integer) = $mul_parity(two_fc, cache, beta);
+                if y_parity != approx_y_parity || (y_integer && significand & 1 != 0) {
+                    significand -= 1;
+                }
+            }
+            (significand, minus_k + $kappa)
+        }
+
+        /// Same as the above for a power of two, whose lower neighbor is
+        /// closer than the upper one
+        #[cold]
+        fn $shorter(e: i32) -> ($uint, i32) {
+            let minus_k = floor_log10_three_quarters_pow2(e);
+            let beta = e + floor_log2_pow10(-minus_k);
+            let cache = $high($table[(-minus_k - $table_min) as usize]);
+
+            // the interval is closed, and its lower bound is an integer only if
+            // `e` is 2 or 3
+            let shift = 64 - $sbits - 1 - beta;
+            let mut xi = ((cache - (cache >> ($sbits + 2))) >> shift) as $uint;
+            let zi = ((cache + (cache >> ($sbits + 1))) >> shift) as $uint;
+            if !(2..=3).contains(&e) {
+                xi += 1;
+            }
+
+            let significand = zi / 10;
+            if significand * 10 >= xi {
+                return (significand, minus_k + 1);
+            }
+
+            // otherwise round the value itself, with a tie only at `$tie`
+            let mut significand = (((cache >> (shift - 1)) + 1) / 2) as $uint;
+            if significand & 1 != 0 && e == $tie {
+                significand -= 1;
+            } else if significand < xi {
+                significand += 1;
+            }
+            (significand, minus_k)
+        }
+    };
+}
+
+impl_to_decimal!(
+    to_decimal32, to_decimal_shorter32, u32,
+    POW10_32, POW10_32_MIN,
+    high32, mul32, mul_parity32,
+    significand_bits: 23,
+    exponent_bias: 127,
+    kappa: 1,
+    divisors: 100, 10,
+    tie_exponent: -35
+);
+
+impl_to_decimal!(
+    to_decimal64, to_decimal_shorter64, u64,
+    POW10_64, POW10_64_MIN,
+    high64, mul64, mul_parity64,
+    significand_bits: 52,
+    exponent_bias: 1023,
+    kappa: 2,
+    divisors: 1000, 100,
+    tie_exponent: -77
+);
+
+#[inline]
+pub(super) fn decimal_length(v: u64) -> isize {
+    let t = (((64 - (v | 1).leading_zeros()) * 1233) >> 12) as usize;
+    (t + (v >= POW10[t]) as usize) as isize
+}
+
+/// Eight digits of `value < 10^8` as ASCII, the leading digit in the lowest
+/// byte, computed in the lanes of a `u64` without divisions
+#[inline]
+fn encode8(value: u32) -> u64 {
+    // four digits in each 32-bit lane, the leading ones in the lower lane
+    let x = (value / 10_000) as u64 | ((value % 10_000) as u64) << 32;
+    // `n * 10486 >> 20` is `n / 100` for `n < 10^4`
+    let hundreds = ((x * 10486) >> 20) & 0x0000_007F_0000_007F;
+    let x = hundreds | (x - hundreds * 100) << 16;
+    // `n * 103 >> 10` is `n / 10` for `n < 100`
+    let tens = ((x * 103) >> 10) & 0x000F_000F_000F_000F;
+    let x = tens | (x - tens * 10) << 8;
+    x | 0x3030_3030_3030_3030
+}
+
+/// Writes the `length` digits of `value`, which may write up to 8 bytes if
+/// `length` is less than 8
+#[inline]
+pub(super) unsafe fn write_digits(mut value: u64, mut length: isize, mut dst: *mut u8) {
+    debug_assert!((1..=17).contains(&length));
+    if length > 16 {
+        *dst = b'0' + (value / 10_000_000_000_000_000) as u8;
+        value %= 10_000_000_000_000_000;
+        length -= 1;
+        dst = dst.add(1);
+    }
+
+    // leading zeros are shifted out of the first block
+    if length > 8 {
+        let high = encode8((value / 100_000_000) as u32) >> (8 * (16 - length));
+        ptr::write_unaligned(dst as *mut u64, high.to_le());
+        let low = encode8((value % 100_000_000) as u32);
+        ptr::write_unaligned(dst.offset(length - 8) as *mut u64, low.to_le());
+    } else {
+        let digits = encode8(value as u32) >> (8 * (8 - length));
+        ptr::write_unaligned(dst as *mut u64, digits.to_le());
+    }
+}
+
+/// Moves `len` bytes from `dst + 1` to `dst` for `1 <= len <= 16` without a
+/// call to `memmove`, by loading the first and last `len / 2` rounded up
+/// to a power of two bytes before storing them
+#[inline]
+unsafe fn shift_left(dst: *mut u8, len: usize) {
+    macro_rules! shift {
+        ($type:ty) => {{
+            let size = core::mem::size_of::<$type>();
+            let head = ptr::read_unaligned(dst.add(1) as *const $type);
+            let tail = ptr::read_unaligned(dst.add(1 + len - size) as *const $type);
+            ptr::write_unaligned(dst as *mut $type, head);
+            ptr::write_unaligned(dst.add(len - size) as *mut $type, tail);
+        }};
+    }
+
+    debug_assert!((1..=16).contains(&len));
+    if len >= 8 {
+        shift!(u64)
+    } else if len >= 4 {
+        shift!(u32)
+    } else if len >= 2 {
+        shift!(u16)
+    } else {
+        *dst = *dst.add(1);
+    }
+}
+
+/// Writes `significand * 10^exponent` in positional notation if the decimal
+/// point is in `min_point + 1..=max_point` digits from the first digit, or
+/// in scientific notation otherwise, and returns the length
+#[inline]
+pub(super) unsafe fn write_decimal(
+    mut significand: u64,
+    mut exponent: i32,
+    max_point: isize,
+    min_point: isize,
+    dst: *mut u8,
+) -> usize {
+    while significand % 10 == 0 {
+        significand /= 10;
+        exponent += 1;
+    }
+
+    let length = decimal_length(significand);
+    let k = exponent as isize;
+    let point = length + k;
+
+    if 0 <= k && point <= max_point {
+        // 1234e7 -> 12340000000.0
+        write_digits(significand, length, dst);
+        ptr::write_bytes(dst.offset(length), b'0', k as usize);
+        copy_nonoverlapping(b".0".as_ptr(), dst.offset(point), 2);
+        point as usize + 2
+    } else if 0 < point && point <= max_point {
+        // 1234e-2 -> 12.34
+        write_digits(significand, length, dst.add(1));
+        shift_left(dst, point as usize);
+        *dst.offset(point) = b'.';
+        length as usize + 1
+    } else if min_point < point && point <= 0 {
+        // 1234e-6 -> 0.001234
+        let offset = 2 - point;
+        copy_nonoverlapping(b"0.".as_ptr(), dst, 2);
+        ptr::write_bytes(dst.add(2), b'0', -point as usize);
+        write_digits(significand, length, dst.offset(offset));
+        (length + offset) as usize
+    } else if length == 1 {
+        // 1e30
+        *dst = b'0' + significand as u8;
+        *dst.add(1) = b'e';
+        2 + ((point - 1) as i32).write_to(dst.add(2))
+    } else {
+        // 1234e30 -> 1.234e33
+        write_digits(significand, length, dst.add(1));
+        *dst = *dst.add(1);
+        *dst.add(1) = b'.';
+        *dst.offset(length + 1) = b'e';
+        length as usize + 2 + ((point - 1) as i32).write_to(dst.offset(length + 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format32, format64, write_digits, POW10};
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    fn check32(value: f32) {
+        let mut buf = [0u8; 16];
+        let len = unsafe { format32(value, buf.as_mut_ptr()) };
+        let output = std::str::from_utf8(&buf[..len]).unwrap();
+        assert_eq!(output, ryu::Buffer::new().format_finite(value));
+        assert_eq!(output.parse::<f32>().unwrap().to_bits(), value.to_bits());
+    }
+
+    fn check64(value: f64) {
+        let mut buf = [0u8; 24];
+        let len = unsafe { format64(value, buf.as_mut_ptr()) };
+        let output = std::str::from_utf8(&buf[..len]).unwrap();
+        assert_eq!(output, ryu::Buffer::new().format_finite(value));
+        assert_eq!(output.parse::<f64>().unwrap().to_bits(), value.to_bits());
+    }
+
+    #[test]
+    fn special_values() {
+        for &value in &[
+            0.0,
+            1.0,
+            0.1,
+            0.3,
+            1e15,
+            1e16,
+            123456.0,
+            2.5e-5,
+            1e-7,
+            16777216.0,
+            16777218.0,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::EPSILON,
+            f32::from_bits(1),
+            f32::from_bits(0x007F_FFFF),
+        ] {
+            check32(value);
+            check32(-value);
+        }
+
+        for &value in &[
+            0.0,
+            1.0,
+            0.1,
+            0.3,
+            1e15,
+            1e16,
+            1e21,
+            1e22,
+            1e23,
+            123456.0,
+            2.5e-5,
+            1e-7,
+            5e-324,
+            9007199254740992.0,
+            9007199254740994.0,
+            9007199254740993e10,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            f64::EPSILON,
+            f64::from_bits(0x000F_FFFF_FFFF_FFFF),
+            -65.61361699999998,
+            43.42027300000001,
+        ] {
+            check64(value);
+            check64(-value);
+        }
+    }
+
+    #[test]
+    fn powers() {
+        for e in -45..=38 {
+            check32(format!("1e{}", e).parse().unwrap());
+        }
+        for e in -126..=127 {
+            check32(2f32.powi(e));
+        }
+        for e in -324..=308 {
+            check64(format!("1e{}", e).parse().unwrap());
+            check64(format!("9.999999999999999e{}", e - 1).parse().unwrap());
+        }
+        for e in -1022..=1023 {
+            check64(2f64.powi(e));
+        }
+        for i in 0..0x7FF {
+            // every exponent with the lowest and highest significands
+            check64(f64::from_bits(i << 52));
+            check64(f64::from_bits(i << 52 | 1));
+            check64(f64::from_bits(i << 52 | ((1 << 52) - 1)));
+        }
+        for i in 0..=2000u32 {
+            check32(i as f32);
+            check64(i as f64 / 1000.0);
+        }
+    }
+
+    #[test]
+    fn random64() {
+        let mut rng = SmallRng::seed_from_u64(25);
+        for _ in 0..1_000_000 {
+            let value = f64::from_bits(rng.gen());
+            if value.is_finite() {
+                check64(value);
+            }
+        }
+    }
+
+    #[test]
+    fn digits() {
+        let mut rng = SmallRng::seed_from_u64(26);
+        for length in 1..=17 {
+            let low = if length == 1 { 0 } else { POW10[length - 1] };
+            for _ in 0..10_000 {
+                let value = rng.gen_range(low..POW10[length]);
+                let mut buf = [0u8; 24];
+                unsafe { write_digits(value, length as isize, buf.as_mut_ptr()) };
+                assert_eq!(&buf[..length], value.to_string().as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn strided32() {
+        // every 4099th bit pattern, the full range is tested by `exhaustive32`
+        for bits in (0..=u32::MAX).step_by(4099) {
+            let value = f32::from_bits(bits);
+            if value.is_finite() {
+                check32(value);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn exhaustive32() {
+        const THREADS: u32 = 16;
+        let handles = (0..THREADS)
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let start = (u32::MAX / THREADS) * t;
+                    let end = if t + 1 == THREADS {
+                        u32::MAX
+                    } else {
+                        (u32::MAX / THREADS) * (t + 1) - 1
+                    };
+                    for bits in start..=end {
+                        let value = f32::from_bits(bits);
+                        if value.is_finite() {
+                            check32(value);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Float formats selected by [`Options`]
+
+mod dragonbox;
+mod tables;
+
+pub(crate) use self::dragonbox::{format32, format64};
+
+use self::dragonbox::{
+    decimal_length, to_decimal32, to_decimal64, write_decimal, write_digits, POW10,
+};
+use crate::options::{FloatFormat, Options};
+use crate::raw::SerializeRaw;
+
+use core::cmp;
+use std::ptr::{self, copy_nonoverlapping};
+
+/// Float written with the float settings of `Options`
+pub struct FormattedFloat<'a, T>(pub T, pub &'a Options);
+
+macro_rules! impl_formatted_float {
+    ($($type:ty => $decimal:ident, $layout:expr;)*) => {
+        $(
+            impl SerializeRaw for FormattedFloat<'_, $type> {
+                #[inline]
+                fn size_hint(&self) -> usize {
+                    match self.1.float_format {
+                        FloatFormat::Shortest => self.0.size_hint(),
+                        _ => formatted_size_hint(self.0 as f64, self.1.float_format),
+                    }
+                }
+
+                #[inline]
+                unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
+                    let options = self.1;
+                    let mut value = self.0;
+                    if options.normalize_negative_zero && value == 0.0 {
+                        value = 0.0;
+                    }
+
+                    match options.float_format {
+                        FloatFormat::Shortest => {
+                            let len = value.write_to_ptr(dst);
+                            if options.trim_integral_floats {
+                                trim_point_zero(dst, len)
+                            } else {
+                                len
+                            }
+                        }
+                        _ if !value.is_finite() => value.write_to_ptr(dst),
+                        FloatFormat::Fixed(decimals) => {
+                            let negative = value.is_sign_negative();
+                            write_fixed(negative, $decimal(value), decimals, options, dst)
+                        }
+                        FloatFormat::Significant(digits) => write_significant(
+                            value.is_sign_negative(),
+                            $decimal(value),
+                            digits,
+                            options,
+                            $layout,
+                            dst,
+                        ),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+// each type is rounded from its own shortest digits and keeps the layout of
+// `format32` or `format64`
+impl_formatted_float! {
+    f32 => decimal32, (13, -6);
+    f64 => decimal64, (16, -5);
+}
+
+fn formatted_size_hint(value: f64, format: FloatFormat) -> usize {
+    match format {
+        // at most 24 bytes, e.g. `-0.0000` followed by 17 digits
+        FloatFormat::Shortest | FloatFormat::Significant(_) => 32,
+        FloatFormat::Fixed(decimals) if value.is_finite() => {
+            // `value < 2^e` has at most `e * log10(2) + 1` integer digits, and
+            // rounding may add one more
+            let exponent = (value.to_bits() >> 52 & 0x7FF) as usize;
+            let integer_digits = exponent.saturating_sub(1022) * 30103 / 100_000 + 2;
+            // digits are written 8 bytes at a time
+            1 + integer_digits + 1 + decimals as usize + 8
+        }
+        FloatFormat::Fixed(_) => 4,
+    }
+}
+
+/// Removes the `.0` of an integral float written in the layout of
+/// `format32` or `format64`
+#[inline]
+unsafe fn trim_point_zero(dst: *mut u8, len: usize) -> usize {
+    if len >= 2 && *dst.add(len - 2) == b'.' && *dst.add(len - 1) == b'0' {
+        len - 2
+    } else {
+        len
+    }
+}
+
+/// Shortest `(significand, exponent)` of a finite `value`, `(0, 0)` for zero
+#[inline]
+fn decimal32(value: f32) -> (u64, i32) {
+    match value.abs().to_bits() {
+        0 => (0, 0),
+        bits => {
+            let (significand, exponent) = to_decimal32(bits);
+            (significand as u64, exponent)
+        }
+    }
+}
+
+/// Shortest `(significand, exponent)` of a finite `value`, `(0, 0)` for zero
+#[inline]
+fn decimal64(value: f64) -> (u64, i32) {
+    match value.abs().to_bits() {
+        0 => (0, 0),
+        bits => to_decimal64(bits),
+    }
+}
+
+/// Drops the last `count` digits of `significand`, rounding to nearest, ties
+/// to even
+#[inline]
+fn round_digits(significand: u64, count: i32) -> u64 {
+    if count <= 0 {
+        return significand;
+    }
+    // the significand has at most 17 digits, less than half of `10^18`
+    let divisor = match POW10.get(count as usize) {
+        Some(&divisor) => divisor,
+        None => return 0,
+    };
+    let (quotient, remainder) = (significand / divisor, significand % divisor);
+    let half = divisor / 2;
+    if remainder > half || remainder == half && quotient & 1 == 1 {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+/// Writes the shortest digits of a finite float rounded to `decimals` digits
+/// after the decimal point
+unsafe fn write_fixed(
+    negative: bool,
+    (significand, exponent): (u64, i32),
+    decimals: u8,
+    options: &Options,
+    dst: *mut u8,
+) -> usize {
+    let (mut significand, mut exponent) = strip_zeros(significand, exponent);
+    let integral = exponent >= 0;
+    let decimals = if options.trim_integral_floats && integral {
+        0
+    } else {
+        decimals as i32
+    };
+
+    let count = -decimals - exponent;
+    if count > 0 {
+        significand = round_digits(significand, count);
+        exponent += count;
+    }
+    if significand == 0 {
+        exponent = 0;
+    }
+
+    // a small negative value rounded to zero
+    let negative = negative && !(options.normalize_negative_zero && significand == 0);
+    *dst = b'-';
+    let dst = dst.add(negative as usize);
+
+    let length = cmp::max(decimal_length(significand), 1);
+    // number of integral digits, and of digits after the point before the
+    // padding
+    let point = length + exponent as isize;
+    let fraction = cmp::max(-exponent, 0) as isize;
+
+    let mut len = if point <= 0 {
+        *dst = b'0';
+        1
+    } else if exponent >= 0 {
+        // 1234e2 -> 123400
+        write_digits(significand, length, dst);
+        ptr::write_bytes(dst.offset(length), b'0', exponent as usize);
+        point as usize
+    } else {
+        // 1234e-2 -> 12
+        write_digits(significand / POW10[fraction as usize], point, dst);
+        point as usize
+    };
+
+    if decimals > 0 {
+        *dst.add(len) = b'.';
+        len += 1;
+        if point <= 0 {
+            // 1234e-6 -> 001234
+            ptr::write_bytes(dst.add(len), b'0', -point as usize);
+            write_digits(significand, length, dst.offset(len as isize - point));
+        } else if fraction > 0 {
+            // 1234e-2 -> 34
+            let digits = significand % POW10[fraction as usize];
+            write_digits(digits, fraction, dst.add(len));
+        }
+        len += fraction as usize;
+
+        let padding = (decimals - fraction as i32) as usize;
+        ptr::write_bytes(dst.add(len), b'0', padding);
+        len += padding;
+    }
+    negative as usize + len
+}
+
+/// Writes the shortest digits of a finite float rounded to `digits`
+/// significant digits, laid out like the shortest representation
+unsafe fn write_significant(
+    negative: bool,
+    (significand, exponent): (u64, i32),
+    digits: u8,
+    options: &Options,
+    (max_point, min_point): (isize, isize),
+    dst: *mut u8,
+) -> usize {
+    let (significand, exponent) = strip_zeros(significand, exponent);
+    *dst = b'-';
+    let dst_digits = dst.add(negative as usize);
+    let len = if significand == 0 {
+        copy_nonoverlapping(b"0.0".as_ptr(), dst_digits, 3);
+        3
+    } else {
+        let digits = cmp::min(cmp::max(digits, 1), 17) as i32;
+        let count = cmp::max(decimal_length(significand) as i32 - digits, 0);
+        let rounded = round_digits(significand, count);
+        write_decimal(rounded, exponent + count, max_point, min_point, dst_digits)
+    };
+
+    // a float which only rounds to an integer keeps its `.0`
+    let integral = exponent >= 0;
+    if options.trim_integral_floats && integral {
+        negative as usize + trim_point_zero(dst_digits, len)
+    } else {
+        negative as usize + len
+    }
+}
+
+/// Removes the trailing zeros of `significand * 10^exponent`
+#[inline]
+fn strip_zeros(mut significand: u64, mut exponent: i32) -> (u64, i32) {
+    while significand != 0 && significand % 10 == 0 {
+        significand /= 10;
+        exponent += 1;
+    }
+    (significand, exponent)
+}
@@ -1,4 +1,5 @@
 #![cfg_attr(evil_json_nightly, feature(core_intrinsics))]
+#![cfg_attr(all(feature = "nightly-simd", evil_json_nightly), feature(portable_simd))]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 #[macro_use]
@@ -7,11 +8,27 @@ mod macros;
 mod api;
 mod bufwrite;
 mod bufwriter;
+mod bytes;
+mod countwriter;
 mod error;
 mod escape;
+mod float;
+mod options;
 mod raw;
-mod ser;
+pub mod ser;
+mod slicewriter;
 mod suffix;
 
-pub use api::{to_string, to_vec, to_writer};
+pub use api::{
+    serialized_len, to_buf_write, to_slice, to_string, to_string_into, to_string_pretty,
+    to_string_with, to_vec, to_vec_into, to_vec_pretty, to_vec_with, to_writer,
+    to_writer_flushed, to_writer_pretty, to_writer_with,
+};
+pub use bufwrite::BufWrite;
+pub use bufwriter::{BufWriter, IntoInnerError};
 pub use error::Error;
+pub use options::{
+    BytesEncoding, EscapeProfile, EscapeTable, FloatFormat, KeyPolicy, NonAsciiPolicy,
+    NonFinitePolicy, Options, SerializerBuilder,
+};
+pub use ser::compact::Serializer;
@@ -7,6 +7,15 @@ macro_rules! tri {
     };
 }
 
+macro_rules! imap {
+    ($expr:expr) => {
+        match $expr {
+            Ok(r) => Ok(r),
+            Err(e) => Err($crate::error::Error::Io(e)),
+        }
+    };
+}
+
 #[cfg(evil_json_nightly)]
 macro_rules! likely {
     ($val:expr) => {
@@ -0,0 +1,495 @@
+//! Runtime settings shared by all serializers
+
+use crate::escape::{Escaper, ESCAPED};
+
+use core::fmt;
+
+/// How map keys which cannot be represented as JSON strings are handled
+///
+/// Strings, chars, integers and floats are always accepted as map keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyPolicy {
+    /// Return [`Error::InvalidKey`](crate::Error::InvalidKey) for any other key type
+    Strict,
+    /// Also accept `bool`, unit, `Option`, unit variants and newtype structs,
+    /// writing their JSON representation as a string (e.g. `"true"`, `"null"`)
+    Stringify,
+}
+
+impl Default for KeyPolicy {
+    #[inline]
+    fn default() -> KeyPolicy {
+        KeyPolicy::Strict
+    }
+}
+
+/// How non-ASCII characters in strings are written
+///
+/// Escaped characters are written as `\uXXXX`, using a UTF-16 surrogate pair
+/// for characters outside the Basic Multilingual Plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonAsciiPolicy {
+    /// Write non-ASCII characters as UTF-8
+    Keep,
+    /// Escape every non-ASCII character, producing pure ASCII output
+    Escape,
+    /// Escape only the characters outside the Basic Multilingual Plane, for
+    /// consumers which cannot handle 4-byte UTF-8 sequences
+    EscapeNonBmp,
+}
+
+impl Default for NonAsciiPolicy {
+    #[inline]
+    fn default() -> NonAsciiPolicy {
+        NonAsciiPolicy::Keep
+    }
+}
+
+/// Set of ASCII characters escaped in strings, in addition to those required
+/// by JSON
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeProfile {
+    /// Escape only control characters, `"` and `\`
+    Json,
+    /// Also escape `<`, `>`, `&`, `'`, U+2028 and U+2029 as `\uXXXX`, so that
+    /// the output can be embedded in HTML `<script>` elements and JavaScript
+    /// source
+    HtmlSafe,
+}
+
+impl Default for EscapeProfile {
+    #[inline]
+    fn default() -> EscapeProfile {
+        EscapeProfile::Json
+    }
+}
+
+/// How NaN and infinite floats are written
+///
+/// JSON has no representation for these values. The policy applies to map
+/// keys as well, which are written as strings in every case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// Write `null`
+    Null,
+    /// Return [`Error::NonFiniteFloat`](crate::Error::NonFiniteFloat)
+    Error,
+    /// Write the strings `"NaN"`, `"Infinity"` and `"-Infinity"`, which
+    /// Python and JSON5 parsers read back as floats
+    String,
+}
+
+impl Default for NonFinitePolicy {
+    #[inline]
+    fn default() -> NonFinitePolicy {
+        NonFinitePolicy::Null
+    }
+}
+
+/// How finite floats are written
+///
+/// Every format writes a valid JSON number. `Fixed` and `Significant` round
+/// the digits written by `Shortest` to nearest, ties to even, so `0.1f32`
+/// is written as `0.1000` by `Fixed(4)` rather than with the digits of its
+/// exact binary value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// Shortest representation which reads back as the same value, e.g.
+    /// `0.1` and `1.0`
+    Shortest,
+    /// Exactly `n` digits after the decimal point, e.g. `Fixed(2)` writes
+    /// `3.14` and `1.00`
+    ///
+    /// The shortest digits are rounded, not the exact binary value. `2.675`
+    /// is stored as `2.67499999999999982236431605997495353221893310546875`
+    /// but written as `2.68`, and `1.005` is a tie which is written as
+    /// `1.00`. `format!("{:.2}", ..)` writes `2.67` and `1.00` instead.
+    Fixed(u8),
+    /// At most `n` significant digits without trailing zeros, e.g.
+    /// `Significant(3)` writes `3.14` and `1.0`
+    ///
+    /// `n` is clamped to `1..=17`, which covers every `f64`. Asking for more
+    /// digits than the shortest representation has writes it unchanged.
+    /// Digits are rounded as with `Fixed`, so `Significant(3)` writes `2.675`
+    /// as `2.68`.
+    Significant(u8),
+}
+
+impl Default for FloatFormat {
+    #[inline]
+    fn default() -> FloatFormat {
+        FloatFormat::Shortest
+    }
+}
+
+/// How byte sequences passed to `serialize_bytes` are written
+///
+/// Every encoding other than `Array` writes a JSON string. Such byte
+/// sequences are also accepted as map keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// Array of numbers, e.g. `[104,105]`
+    Array,
+    /// Standard base64 with padding (RFC 4648 section 4), e.g. `"aGk="`
+    Base64,
+    /// URL-safe base64 without padding (RFC 4648 section 5), e.g. `"aGk"`
+    Base64UrlNoPad,
+    /// Lower-case hex digits, e.g. `"6869"`
+    HexLower,
+    /// Upper-case hex digits
+    HexUpper,
+}
+
+impl Default for BytesEncoding {
+    #[inline]
+    fn default() -> BytesEncoding {
+        BytesEncoding::Array
+    }
+}
+
+/// Escape sequences of ASCII characters in strings
+///
+/// Each entry is `[0, 0]` to write the byte as is, `*b"\\u"` to write
+/// `\u00XX`, or another two-byte escape sequence such as `*b"\\n"`. The
+/// table is applied before [`EscapeProfile`] and [`NonAsciiPolicy`].
+///
+/// On CPUs without SSSE3, strings are scanned several bytes at a time by
+/// comparing them with every run of consecutive printable characters which
+/// are escaped, counting those of [`EscapeProfile::HtmlSafe`]. Scattered
+/// characters such as `a`, `c` and `e` make scanning slower than a single
+/// run such as `a` to `z`.
+///
+/// ```
+/// use evil_json::{EscapeTable, SerializerBuilder};
+///
+/// let table = EscapeTable::new()
+///     .escape(b'/', *b"\\/")
+///     .escape(0x7F, *b"\\u")
+///     .uppercase_hex(true);
+/// let options = SerializerBuilder::new().escape_table(table).build();
+///
+/// let json = evil_json::to_string_with("</a>\x7f\x1b", &options).unwrap();
+/// assert_eq!(json, r#""<\/a>\u007F\u001B""#);
+/// ```
+#[derive(Clone)]
+pub struct EscapeTable {
+    pub(crate) entries: [[u8; 2]; 256],
+    pub(crate) uppercase_hex: bool,
+}
+
+impl EscapeTable {
+    /// Returns the standard JSON escape table
+    #[inline]
+    pub fn new() -> EscapeTable {
+        EscapeTable {
+            entries: ESCAPED,
+            uppercase_hex: false,
+        }
+    }
+
+    /// Creates a table from its entries
+    ///
+    /// # Panics
+    ///
+    /// Panics if an entry is neither `\u` nor the short JSON escape sequence
+    /// of its own byte (e.g. `\n` for `0x0A`), a control character, `"` or
+    /// `\` is not escaped, or a byte from `0x80` is escaped.
+    pub fn from_array(entries: [[u8; 2]; 256]) -> EscapeTable {
+        for (byte, &entry) in entries.iter().enumerate() {
+            check_entry(byte as u8, entry);
+        }
+
+        EscapeTable {
+            entries,
+            uppercase_hex: false,
+        }
+    }
+
+    /// Sets the escape sequence of `byte`
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same conditions as [`from_array`](Self::from_array).
+    pub fn escape(mut self, byte: u8, entry: [u8; 2]) -> EscapeTable {
+        check_entry(byte, entry);
+        self.entries[byte as usize] = entry;
+        self
+    }
+
+    /// Writes hex digits of `\uXXXX` in upper case
+    #[inline]
+    pub fn uppercase_hex(mut self, uppercase_hex: bool) -> EscapeTable {
+        self.uppercase_hex = uppercase_hex;
+        self
+    }
+
+    #[inline]
+    pub fn entries(&self) -> &[[u8; 2]; 256] {
+        &self.entries
+    }
+}
+
+fn check_entry(byte: u8, entry: [u8; 2]) {
+    let valid = match entry {
+        [0, 0] => byte >= 0x20 && byte != b'"' && byte != b'\\',
+        [b'\\', b'u'] => byte < 0x80,
+        [b'\\', c] => unescape(c) == Some(byte),
+        _ => false,
+    };
+    assert!(
+        valid,
+        "invalid escape sequence {:?} for byte {:#04x}",
+        entry, byte
+    );
+}
+
+/// Returns the byte written as the short escape sequence `\c`
+fn unescape(c: u8) -> Option<u8> {
+    match c {
+        b'"' | b'\\' | b'/' => Some(c),
+        b'b' => Some(0x08),
+        b'f' => Some(0x0C),
+        b'n' => Some(b'\n'),
+        b'r' => Some(b'\r'),
+        b't' => Some(b'\t'),
+        _ => None,
+    }
+}
+
+impl Default for EscapeTable {
+    #[inline]
+    fn default() -> EscapeTable {
+        EscapeTable::new()
+    }
+}
+
+impl fmt::Debug for EscapeTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Entries<'a>(&'a [[u8; 2]; 256]);
+
+        impl<'a> fmt::Debug for Entries<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let escaped = self.0.iter().enumerate().filter(|(_, e)| **e != [0, 0]);
+                f.debug_map()
+                    .entries(escaped.map(|(byte, e)| {
+                        (byte as u8 as char, [e[0] as char, e[1] as char])
+                    }))
+                    .finish()
+            }
+        }
+
+        f.debug_struct("EscapeTable")
+            .field("entries", &Entries(&self.entries))
+            .field("uppercase_hex", &self.uppercase_hex)
+            .finish()
+    }
+}
+
+/// Output settings for [`Serializer`](crate::Serializer) and
+/// [`pretty::Serializer`](crate::ser::pretty::Serializer)
+///
+/// Use [`SerializerBuilder`] to create customized options.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub(crate) key_policy: KeyPolicy,
+    pub(crate) non_ascii: NonAsciiPolicy,
+    pub(crate) escape_profile: EscapeProfile,
+    pub(crate) escape_table: Option<Box<EscapeTable>>,
+    pub(crate) bytes_encoding: BytesEncoding,
+    pub(crate) non_finite: NonFinitePolicy,
+    pub(crate) float_format: FloatFormat,
+    pub(crate) trim_integral_floats: bool,
+    pub(crate) normalize_negative_zero: bool,
+    /// Escape table built from the settings above, or `None` for the
+    /// standard JSON escapes
+    pub(crate) escaper: Option<Box<Escaper>>,
+}
+
+impl Options {
+    pub(crate) const DEFAULT: Options = Options {
+        key_policy: KeyPolicy::Strict,
+        non_ascii: NonAsciiPolicy::Keep,
+        escape_profile: EscapeProfile::Json,
+        escape_table: None,
+        bytes_encoding: BytesEncoding::Array,
+        non_finite: NonFinitePolicy::Null,
+        float_format: FloatFormat::Shortest,
+        trim_integral_floats: false,
+        normalize_negative_zero: false,
+        escaper: None,
+    };
+
+    #[inline]
+    pub fn key_policy(&self) -> KeyPolicy {
+        self.key_policy
+    }
+
+    #[inline]
+    pub fn non_ascii(&self) -> NonAsciiPolicy {
+        self.non_ascii
+    }
+
+    #[inline]
+    pub fn escape_profile(&self) -> EscapeProfile {
+        self.escape_profile
+    }
+
+    /// Returns the custom escape table, or `None` for the standard one
+    #[inline]
+    pub fn escape_table(&self) -> Option<&EscapeTable> {
+        self.escape_table.as_deref()
+    }
+
+    #[inline]
+    pub fn bytes_encoding(&self) -> BytesEncoding {
+        self.bytes_encoding
+    }
+
+    #[inline]
+    pub fn non_finite(&self) -> NonFinitePolicy {
+        self.non_finite
+    }
+
+    #[inline]
+    pub fn float_format(&self) -> FloatFormat {
+        self.float_format
+    }
+
+    #[inline]
+    pub fn trim_integral_floats(&self) -> bool {
+        self.trim_integral_floats
+    }
+
+    #[inline]
+    pub fn normalize_negative_zero(&self) -> bool {
+        self.normalize_negative_zero
+    }
+
+    /// Returns whether floats are written as by
+    /// [`Formatter`](crate::ser::Formatter), which is bypassed otherwise
+    #[inline]
+    pub(crate) fn default_floats(&self) -> bool {
+        self.float_format == FloatFormat::Shortest
+            && !self.trim_integral_floats
+            && !self.normalize_negative_zero
+    }
+
+    #[inline]
+    pub(crate) fn escaper(&self) -> Option<&Escaper> {
+        self.escaper.as_deref()
+    }
+}
+
+impl Default for Options {
+    #[inline]
+    fn default() -> Options {
+        Options::DEFAULT
+    }
+}
+
+pub(crate) static DEFAULT_OPTIONS: Options = Options::DEFAULT;
+
+/// Builder for [`Options`]
+///
+/// ```
+/// use evil_json::{KeyPolicy, Serializer, SerializerBuilder};
+/// use serde::Serialize;
+/// use std::collections::BTreeMap;
+///
+/// let options = SerializerBuilder::new()
+///     .key_policy(KeyPolicy::Stringify)
+///     .build();
+///
+/// let mut map = BTreeMap::new();
+/// map.insert(true, 1);
+///
+/// let mut buf = Vec::new();
+/// map.serialize(&mut Serializer::with_options(&mut buf, &options)).unwrap();
+/// assert_eq!(buf, br#"{"true":1}"#);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SerializerBuilder {
+    options: Options,
+}
+
+impl SerializerBuilder {
+    #[inline]
+    pub fn new() -> SerializerBuilder {
+        SerializerBuilder {
+            options: Options::DEFAULT,
+        }
+    }
+
+    #[inline]
+    pub fn key_policy(mut self, key_policy: KeyPolicy) -> SerializerBuilder {
+        self.options.key_policy = key_policy;
+        self
+    }
+
+    #[inline]
+    pub fn non_ascii(mut self, non_ascii: NonAsciiPolicy) -> SerializerBuilder {
+        self.options.non_ascii = non_ascii;
+        self
+    }
+
+    #[inline]
+    pub fn escape_profile(mut self, escape_profile: EscapeProfile) -> SerializerBuilder {
+        self.options.escape_profile = escape_profile;
+        self
+    }
+
+    #[inline]
+    pub fn escape_table(mut self, escape_table: EscapeTable) -> SerializerBuilder {
+        self.options.escape_table = Some(Box::new(escape_table));
+        self
+    }
+
+    #[inline]
+    pub fn bytes_encoding(mut self, bytes_encoding: BytesEncoding) -> SerializerBuilder {
+        self.options.bytes_encoding = bytes_encoding;
+        self
+    }
+
+    #[inline]
+    pub fn non_finite(mut self, non_finite: NonFinitePolicy) -> SerializerBuilder {
+        self.options.non_finite = non_finite;
+        self
+    }
+
+    #[inline]
+    pub fn float_format(mut self, float_format: FloatFormat) -> SerializerBuilder {
+        self.options.float_format = float_format;
+        self
+    }
+
+    /// Writes floats without a fractional part without the trailing `.0`
+    /// (or `.00` with [`FloatFormat::Fixed`]), e.g. `1` instead of `1.0`
+    ///
+    /// Floats which only round to an integer keep their decimals, e.g.
+    /// `1.001` is written as `1.00` with `Fixed(2)`.
+    #[inline]
+    pub fn trim_integral_floats(mut self, trim: bool) -> SerializerBuilder {
+        self.options.trim_integral_floats = trim;
+        self
+    }
+
+    /// Writes negative zero, and negative values rounded to zero, without the
+    /// minus sign
+    #[inline]
+    pub fn normalize_negative_zero(mut self, normalize: bool) -> SerializerBuilder {
+        self.options.normalize_negative_zero = normalize;
+        self
+    }
+
+    pub fn build(mut self) -> Options {
+        self.options.escaper = Escaper::new(&self.options).map(Box::new);
+        self.options
+    }
+}
+
+impl From<SerializerBuilder> for Options {
+    #[inline]
+    fn from(builder: SerializerBuilder) -> Options {
+        builder.build()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::float::{format32, format64};
 use itoap::Integer;
 use std::ptr::copy_nonoverlapping;
 
@@ -61,6 +62,18 @@ macro_rules! impl_integers {
 
 impl_integers!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
 
+/// Spelling of a NaN or infinite float used by JSON5 and Python
+#[cold]
+pub(crate) fn non_finite_str(value: f64) -> &'static str {
+    if value.is_nan() {
+        "NaN"
+    } else if value > 0.0 {
+        "Infinity"
+    } else {
+        "-Infinity"
+    }
+}
+
 impl SerializeRaw for f32 {
     #[inline]
     fn size_hint(&self) -> usize {
@@ -70,7 +83,7 @@ impl SerializeRaw for f32 {
     #[inline]
     unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize {
         if self.is_finite() {
-            ryu::raw::format32(*self, dst)
+            format32(*self, dst)
         } else {
             copy_nonoverlapping(b"null".as_ptr(), dst, 4);
             4
@@ -87,7 +100,7 @@ impl SerializeRaw for f64 {
     #[inline]
     unsafe fn write_to_ptr(&self, dst: *mut u8) -> usize  {
         if self.is_finite() {
-            ryu::raw::format64(*self, dst)
+            format64(*self, dst)
         } else {
             copy_nonoverlapping(b"null".as_ptr(), dst, 4);
             4
@@ -1,134 +1,507 @@
+use crate::bufwrite::BufWrite;
+use crate::bytes::{as_byte_slice, write_array, write_encoded};
 use crate::error::Error;
-use crate::escape::{escape, escape_cold, need_escape};
-use crate::raw::RawStr;
+use crate::escape::{
+    escape, escape_char, escape_cold, need_escape_with, write_escaped, write_quoted,
+    EscapedChar, QuotedChar,
+};
+use crate::float::FormattedFloat;
+use crate::options::{
+    BytesEncoding, KeyPolicy, NonFinitePolicy, Options, DEFAULT_OPTIONS,
+};
+use crate::raw::{non_finite_str, RawStr};
+use crate::ser::format::{CompactFormatter, Formatter};
 use crate::suffix::{MapSuffix, RootSuffix, SeqSuffix, Suffix};
-use crate::{bufwrite::BufWrite, escape::escape_char};
 
 use serde::ser;
 use std::marker::PhantomData;
 
-macro_rules! imap {
-    ($expr:expr) => {
-        match $expr {
-            Ok(r) => Ok(r),
-            Err(e) => Err(Error::Io(e)),
-        }
+/// Serializer which writes JSON into a buffered sink such as `Vec<u8>`
+///
+/// Every token is written through the [`Formatter`] `F`. With the default
+/// [`CompactFormatter`] and the writers of this crate, separators are written
+/// together with the value before them, and the last one is removed when the
+/// container is closed.
+///
+/// ```
+/// use evil_json::Serializer;
+/// use serde::Serialize;
+///
+/// let mut buf = Vec::new();
+/// (1, "two").serialize(&mut Serializer::new(&mut buf)).unwrap();
+/// assert_eq!(buf, br#"[1,"two"]"#);
+/// ```
+#[derive(Debug)]
+pub struct Serializer<'w, W: BufWrite + 'w, F: Formatter = CompactFormatter> {
+    writer: &'w mut W,
+    formatter: F,
+    options: &'w Options,
+}
+
+/// Whether separators are written together with the value before them, and
+/// the last one is removed or rewritten when the container is closed
+///
+/// This needs the compact output and a writer which keeps its last bytes in
+/// memory. Otherwise every token goes through the formatter hooks, which
+/// never take back committed output.
+macro_rules! suffixed {
+    () => {
+        F::COMPACT && W::KEEPS_TAIL
     };
 }
 
-#[derive(Debug)]
-pub(crate) struct Serializer<'w, W: BufWrite + 'w, S: Suffix = RootSuffix> {
-    writer: &'w mut W,
-    _suffix: PhantomData<S>,
+impl<'w, W: BufWrite> Serializer<'w, W> {
+    #[inline]
+    pub fn new(writer: &'w mut W) -> Self {
+        Self::with_options(writer, &DEFAULT_OPTIONS)
+    }
+
+    #[inline]
+    pub fn with_options(writer: &'w mut W, options: &'w Options) -> Self {
+        Self::with_formatter_and_options(writer, CompactFormatter, options)
+    }
 }
 
-impl<'w, W: BufWrite> Serializer<'w, W, RootSuffix> {
+impl<'w, W: BufWrite, F: Formatter> Serializer<'w, W, F> {
     #[inline]
-    pub(crate) fn new(writer: &'w mut W) -> Self {
+    pub fn with_formatter(writer: &'w mut W, formatter: F) -> Self {
+        Self::with_formatter_and_options(writer, formatter, &DEFAULT_OPTIONS)
+    }
+
+    #[inline]
+    pub fn with_formatter_and_options(
+        writer: &'w mut W,
+        formatter: F,
+        options: &'w Options,
+    ) -> Self {
         Self {
             writer,
+            formatter,
+            options,
+        }
+    }
+
+    #[inline]
+    pub fn options(&self) -> &Options {
+        self.options
+    }
+
+    #[inline]
+    pub fn formatter(&mut self) -> &mut F {
+        &mut self.formatter
+    }
+
+    #[inline]
+    pub fn into_formatter(self) -> F {
+        self.formatter
+    }
+
+    /// Returns a serializer which writes `S::SUFFIX` after the value
+    #[inline]
+    fn value<S: Suffix>(&mut self) -> ValueSerializer<'_, 'w, W, F, S> {
+        ValueSerializer {
+            ser: self,
             _suffix: PhantomData,
         }
     }
+
+    /// Writes a NaN or infinite float unless the policy is `Null`, which is
+    /// written like finite values
+    #[cold]
+    fn serialize_non_finite(
+        &mut self,
+        value: f64,
+        suffix: &'static str,
+    ) -> Result<(), Error> {
+        match self.options.non_finite {
+            NonFinitePolicy::String if suffixed!() => imap!(self.writer.write4(
+                &RawStr("\""),
+                &RawStr(non_finite_str(value)),
+                &RawStr("\""),
+                &RawStr(suffix)
+            )),
+            NonFinitePolicy::String => self.write_static_str(non_finite_str(value)),
+            NonFinitePolicy::Null | NonFinitePolicy::Error => Err(Error::NonFiniteFloat),
+        }
+    }
+
+    /// Writes a string through the formatter, escaping it only if needed
+    fn write_static_str(&mut self, value: &'static str) -> Result<(), Error> {
+        self.formatter.begin_string(self.writer)?;
+        if !need_escape_with(value, self.options.escaper()) {
+            self.writer.write1(&RawStr(value))?;
+        } else {
+            escape_cold(self.writer, value, self.options.escaper())?;
+        }
+        imap!(self.formatter.end_string(self.writer))
+    }
+
+    /// Writes a float through the formatter unless `Options` selects a
+    /// different float format
+    #[inline]
+    fn write_f32(&mut self, value: f32) -> Result<(), Error> {
+        if self.options.default_floats() {
+            imap!(self.formatter.write_f32(self.writer, value))
+        } else {
+            imap!(self.writer.write1(&FormattedFloat(value, self.options)))
+        }
+    }
+
+    #[inline]
+    fn write_f64(&mut self, value: f64) -> Result<(), Error> {
+        if self.options.default_floats() {
+            imap!(self.formatter.write_f64(self.writer, value))
+        } else {
+            imap!(self.writer.write1(&FormattedFloat(value, self.options)))
+        }
+    }
+
+    /// Writes `{"variant":` through the formatter and leaves the object open
+    fn begin_variant(&mut self, variant: &'static str) -> Result<(), Error> {
+        self.formatter.begin_object(self.writer)?;
+        self.formatter.begin_object_key(self.writer, true)?;
+        tri!(self.write_static_str(variant));
+        self.formatter.end_object_key(self.writer)?;
+        imap!(self.formatter.begin_object_value(self.writer))
+    }
+
+    #[inline]
+    fn end_variant(&mut self) -> Result<(), Error> {
+        self.formatter.end_object_value(self.writer)?;
+        imap!(self.formatter.end_object(self.writer))
+    }
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident($($arg:ident: $type:ty),*) -> $ok:ty;)*) => {
+        $(
+            #[inline]
+            fn $method(self, $($arg: $type),*) -> Result<$ok, Self::Error> {
+                self.value::<RootSuffix>().$method($($arg),*)
+            }
+        )*
+    };
 }
 
-impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
-    for &'a mut Serializer<'w, W, S>
+impl<'a, 'w: 'a, W: BufWrite, F: Formatter> ser::Serializer
+    for &'a mut Serializer<'w, W, F>
 {
     type Ok = ();
     type Error = Error;
-    type SerializeSeq = SeqSerializer<'a, W, S>;
-    type SerializeTuple = TupleSerializer<'a, W, S>;
-    type SerializeTupleStruct = TupleSerializer<'a, W, S>;
-    type SerializeTupleVariant = TupleSerializer<'a, W, S>;
-    type SerializeMap = MapSerializer<'a, W, S>;
-    type SerializeStruct = StructSerializer<'a, W, S>;
-    type SerializeStructVariant = StructSerializer<'a, W, S>;
+    type SerializeSeq = SeqSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeTuple = TupleSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeTupleStruct = TupleSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeTupleVariant = TupleSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeMap = MapSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeStruct = StructSerializer<'a, 'w, W, F, RootSuffix>;
+    type SerializeStructVariant = StructSerializer<'a, 'w, W, F, RootSuffix>;
+
+    forward_to_value! {
+        serialize_bool(v: bool) -> ();
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_u8(v: u8) -> ();
+        serialize_u16(v: u16) -> ();
+        serialize_u32(v: u32) -> ();
+        serialize_u64(v: u64) -> ();
+        serialize_i128(v: i128) -> ();
+        serialize_u128(v: u128) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_str(v: &str) -> ();
+        serialize_bytes(v: &[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(name: &'static str) -> ();
+        serialize_unit_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str
+        ) -> ();
+        serialize_seq(len: Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(len: usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Self::SerializeTupleVariant;
+        serialize_map(len: Option<usize>) -> Self::SerializeMap;
+        serialize_struct(name: &'static str, len: usize) -> Self::SerializeStruct;
+        serialize_struct_variant(
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Self::SerializeStructVariant;
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::Serializer::serialize_newtype_variant(
+            self.value::<RootSuffix>(),
+            name,
+            variant_index,
+            variant,
+            value,
+        )
+    }
+
+    #[inline]
+    fn collect_seq<I>(self, iter: I) -> Result<Self::Ok, Self::Error>
+    where
+        I: IntoIterator,
+        I::Item: ser::Serialize,
+    {
+        self.value::<RootSuffix>().collect_seq(iter)
+    }
+}
+
+/// Serializer for a single value which is followed by `S::SUFFIX`
+///
+/// The suffix is only written by [`CompactFormatter`]; other formatters write
+/// separators through their hooks.
+#[doc(hidden)]
+pub struct ValueSerializer<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> {
+    ser: &'a mut Serializer<'w, W, F>,
+    _suffix: PhantomData<S>,
+}
+
+impl<'a, 'w: 'a, W: BufWrite, F: Formatter, S: Suffix> ser::Serializer
+    for ValueSerializer<'a, 'w, W, F, S>
+{
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a, 'w, W, F, S>;
+    type SerializeTuple = TupleSerializer<'a, 'w, W, F, S>;
+    type SerializeTupleStruct = TupleSerializer<'a, 'w, W, F, S>;
+    type SerializeTupleVariant = TupleSerializer<'a, 'w, W, F, S>;
+    type SerializeMap = MapSerializer<'a, 'w, W, F, S>;
+    type SerializeStruct = StructSerializer<'a, 'w, W, F, S>;
+    type SerializeStructVariant = StructSerializer<'a, 'w, W, F, S>;
 
     #[inline]
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_bool(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_i8(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_i16(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_i32(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_i64(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_u8(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_u16(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_u32(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_u64(ser.writer, v))
+        }
     }
 
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_i128(ser.writer, v))
+        }
     }
 
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&v, &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_u128(ser.writer, v))
+        }
     }
 
     #[inline]
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if likely!(v.is_finite()) || ser.options.non_finite == NonFinitePolicy::Null {
+            if suffixed!() {
+                imap!(ser
+                    .writer
+                    .write2(&FormattedFloat(v, ser.options), &RawStr(S::SUFFIX)))
+            } else {
+                ser.write_f32(v)
+            }
+        } else {
+            ser.serialize_non_finite(v as f64, S::SUFFIX)
+        }
     }
 
     #[inline]
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if likely!(v.is_finite()) || ser.options.non_finite == NonFinitePolicy::Null {
+            if suffixed!() {
+                imap!(ser
+                    .writer
+                    .write2(&FormattedFloat(v, ser.options), &RawStr(S::SUFFIX)))
+            } else {
+                ser.write_f64(v)
+            }
+        } else {
+            ser.serialize_non_finite(v, S::SUFFIX)
+        }
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        // TODO: pre-allocate all things
-        self.writer.write_all(b"\"")?;
-        escape_char(self.writer, v)?;
-        imap!(self.writer.write2(&RawStr("\""), &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser
+                .writer
+                .write2(&QuotedChar(v, ser.options.escaper()), &RawStr(S::SUFFIX)))
+        } else {
+            ser.formatter.begin_string(ser.writer)?;
+            escape_char(ser.writer, v, ser.options.escaper())?;
+            imap!(ser.formatter.end_string(ser.writer))
+        }
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(b"\"")?;
-
-        // TODO: implement escape with suffix
-        match escape(self.writer, v) {
-            Ok(_) => imap!(self.writer.write2(&RawStr("\""), &RawStr(S::SUFFIX))),
-            Err(e) => Err(Error::Io(e)),
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(write_quoted(
+                ser.writer,
+                v,
+                S::SUFFIX,
+                ser.options.escaper()
+            ))
+        } else {
+            ser.formatter.begin_string(ser.writer)?;
+            escape(ser.writer, v, ser.options.escaper())?;
+            imap!(ser.formatter.end_string(ser.writer))
         }
     }
 
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        // TODO: specialize for byte sequence
-        ser::Serialize::serialize(v, self)
+        match self.ser.options.bytes_encoding {
+            BytesEncoding::Array if suffixed!() => {
+                imap!(write_array(self.ser.writer, v, S::SUFFIX))
+            }
+            BytesEncoding::Array => ser::Serialize::serialize(v, self),
+            encoding if suffixed!() => {
+                let ser = self.ser;
+                ser.writer.write_all(b"\"")?;
+                write_encoded(ser.writer, v, encoding)?;
+                imap!(ser.writer.write2(&RawStr("\""), &RawStr(S::SUFFIX)))
+            }
+            encoding => {
+                let ser = self.ser;
+                ser.formatter.begin_string(ser.writer)?;
+                write_encoded(ser.writer, v, encoding)?;
+                imap!(ser.formatter.end_string(ser.writer))
+            }
+        }
     }
 
     #[inline]
@@ -137,16 +510,21 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
     }
 
     #[inline]
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
 
     #[inline]
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&RawStr("null"), &RawStr(S::SUFFIX)))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser.writer.write2(&RawStr("null"), &RawStr(S::SUFFIX)))
+        } else {
+            imap!(ser.formatter.write_null(ser.writer))
+        }
     }
 
     #[inline]
@@ -161,18 +539,21 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        if !need_escape(variant) {
-            imap!(self.writer.write4(
+        let ser = self.ser;
+        if !suffixed!() {
+            ser.write_static_str(variant)
+        } else if !need_escape_with(variant, ser.options.escaper()) {
+            imap!(ser.writer.write4(
                 &RawStr("\""),
                 &RawStr(variant),
                 &RawStr("\""),
                 &RawStr(S::SUFFIX)
             ))
         } else {
-            self.writer.write_all(b"\"")?;
-            match escape_cold(self.writer, variant) {
+            ser.writer.write_all(b"\"")?;
+            match escape_cold(ser.writer, variant, ser.options.escaper()) {
                 Ok(_) => {
-                    imap!(self.writer.write2(&RawStr("\""), &RawStr(S::SUFFIX)))
+                    imap!(ser.writer.write2(&RawStr("\""), &RawStr(S::SUFFIX)))
                 }
                 Err(e) => Err(Error::Io(e)),
             }
@@ -180,19 +561,19 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
     }
 
     #[inline]
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         value.serialize(self)
     }
 
     #[cfg_attr(not(debug_assertions), inline(always))]
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -200,56 +581,63 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        if !need_escape(variant) {
-            self.writer
+        let ser = self.ser;
+        if !suffixed!() {
+            tri!(ser.begin_variant(variant));
+            tri!(value.serialize(ser.value::<RootSuffix>()));
+            return ser.end_variant();
+        }
+
+        if !need_escape_with(variant, ser.options.escaper()) {
+            ser.writer
                 .write3(&RawStr("{\""), &RawStr(variant), &RawStr("\":"))?;
         } else {
-            self.writer.write_all(b"{\"")?;
-            match escape_cold(self.writer, variant) {
+            ser.writer.write_all(b"{\"")?;
+            match escape_cold(ser.writer, variant, ser.options.escaper()) {
                 Ok(_) => {
-                    self.writer.write_all(b"\":")?;
+                    ser.writer.write_all(b"\":")?;
                 }
                 Err(e) => return Err(Error::Io(e)),
             }
         }
 
-        tri!(value.serialize(&mut Serializer {
-            writer: self.writer,
-            _suffix: PhantomData::<RootSuffix>
-        }));
-        imap!(self.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
+        tri!(value.serialize(ser.value::<RootSuffix>()));
+        imap!(ser.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
     }
 
     fn serialize_seq(
         self,
         len: Option<usize>,
     ) -> Result<Self::SerializeSeq, Self::Error> {
-        let first = len.map_or(true, |len| len == 0);
-        self.writer.write_all(b"[")?;
+        let ser = self.ser;
+        let first = if suffixed!() {
+            ser.writer.write_all(b"[")?;
+            len.map_or(true, |len| len == 0)
+        } else {
+            ser.formatter.begin_array(ser.writer)?;
+            true
+        };
         Ok(SeqSerializer {
-            inner: Serializer::<'a, W, SeqSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
+            ser,
             first,
             _suffix: PhantomData,
         })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        if len != 0 {
-            self.writer.write_all(b"[")?;
+        let ser = self.ser;
+        if !suffixed!() {
+            ser.formatter.begin_array(ser.writer)?;
+        } else if len != 0 {
+            ser.writer.write_all(b"[")?;
         } else {
-            self.writer.write2(&RawStr("[]"), &RawStr(S::SUFFIX))?;
+            ser.writer.write2(&RawStr("[]"), &RawStr(S::SUFFIX))?;
         }
         Ok(TupleSerializer {
-            inner: Serializer::<'a, W, SeqSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
-            first: len == 0,
+            ser,
+            first: !suffixed!() || len == 0,
             _suffix: PhantomData,
         })
     }
@@ -260,7 +648,7 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        <Self as ser::Serializer>::serialize_tuple(self, len)
+        self.serialize_tuple(len)
     }
 
     #[cfg_attr(not(debug_assertions), inline(always))]
@@ -271,12 +659,16 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        if !need_escape(variant) {
+        let ser = self.ser;
+        if !suffixed!() {
+            tri!(ser.begin_variant(variant));
+            ser.formatter.begin_array(ser.writer)?;
+        } else if !need_escape_with(variant, ser.options.escaper()) {
             if len != 0 {
-                self.writer
+                ser.writer
                     .write3(&RawStr("{\""), &RawStr(variant), &RawStr("\":["))?;
             } else {
-                self.writer.write4(
+                ser.writer.write4(
                     &RawStr("{\""),
                     &RawStr(variant),
                     &RawStr("\":[]}"),
@@ -284,13 +676,13 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
                 )?;
             }
         } else {
-            self.writer.write_all(b"{\"")?;
-            match escape_cold(self.writer, variant) {
+            ser.writer.write_all(b"{\"")?;
+            match escape_cold(ser.writer, variant, ser.options.escaper()) {
                 Ok(_) => {
                     if len != 0 {
-                        self.writer.write_all(b"\":[")?;
+                        ser.writer.write_all(b"\":[")?;
                     } else {
-                        self.writer.write2(&RawStr("\":[]}"), &RawStr(S::SUFFIX))?;
+                        ser.writer.write2(&RawStr("\":[]}"), &RawStr(S::SUFFIX))?;
                     }
                 }
                 Err(e) => return Err(Error::Io(e)),
@@ -298,11 +690,8 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         }
 
         Ok(TupleSerializer {
-            inner: Serializer::<'a, W, SeqSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
-            first: len == 0,
+            ser,
+            first: !suffixed!() || len == 0,
             _suffix: PhantomData,
         })
     }
@@ -311,13 +700,16 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         self,
         len: Option<usize>,
     ) -> Result<Self::SerializeMap, Self::Error> {
-        let first = len.map_or(true, |len| len == 0);
-        self.writer.write_all(b"{\"")?;
+        let ser = self.ser;
+        let first = if suffixed!() {
+            ser.writer.write_all(b"{\"")?;
+            len.map_or(true, |len| len == 0)
+        } else {
+            ser.formatter.begin_object(ser.writer)?;
+            true
+        };
         Ok(MapSerializer {
-            inner: Serializer::<'a, W, MapSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
+            ser,
             first,
             _suffix: PhantomData,
         })
@@ -328,13 +720,16 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        let first = len == 0;
-        self.writer.write_all(b"{\"")?;
+        let ser = self.ser;
+        let first = if suffixed!() {
+            ser.writer.write_all(b"{\"")?;
+            len == 0
+        } else {
+            ser.formatter.begin_object(ser.writer)?;
+            true
+        };
         Ok(StructSerializer {
-            inner: Serializer::<'a, W, MapSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
+            ser,
             first,
             _suffix: PhantomData,
         })
@@ -348,25 +743,26 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        if !need_escape(variant) {
-            self.writer
+        let ser = self.ser;
+        if !suffixed!() {
+            tri!(ser.begin_variant(variant));
+            ser.formatter.begin_object(ser.writer)?;
+        } else if !need_escape_with(variant, ser.options.escaper()) {
+            ser.writer
                 .write3(&RawStr("{\""), &RawStr(variant), &RawStr("\":{\""))?;
         } else {
-            self.writer.write_all(b"{\"")?;
-            match escape_cold(self.writer, variant) {
+            ser.writer.write_all(b"{\"")?;
+            match escape_cold(ser.writer, variant, ser.options.escaper()) {
                 Ok(_) => {
-                    self.writer.write_all(b"\":{\"")?;
+                    ser.writer.write_all(b"\":{\"")?;
                 }
                 Err(e) => return Err(Error::Io(e)),
             }
         }
 
         Ok(StructSerializer {
-            inner: Serializer::<'a, W, MapSuffix> {
-                writer: self.writer,
-                _suffix: PhantomData,
-            },
-            first: len == 0,
+            ser,
+            first: !suffixed!() || len == 0,
             _suffix: PhantomData,
         })
     }
@@ -376,72 +772,104 @@ impl<'a, 'w: 'a, W: BufWrite, S: Suffix> ser::Serializer
         I: IntoIterator,
         I::Item: ser::Serialize,
     {
-        self.writer.write_all(b"[")?;
-
         let it = iter.into_iter();
-        let mut first = true;
-        let mut ser = Serializer {
-            writer: self.writer,
-            _suffix: PhantomData::<SeqSuffix>,
-        };
+        if !suffixed!() {
+            let mut seq = tri!(self.serialize_seq(None));
+            for elem in it {
+                tri!(ser::SerializeSeq::serialize_element(&mut seq, &elem));
+            }
+            return ser::SerializeSeq::end(seq);
+        }
+
+        let ser = self.ser;
+        if let Some(bytes) = as_byte_slice(&it) {
+            return imap!(write_array(ser.writer, bytes, S::SUFFIX));
+        }
 
+        ser.writer.write_all(b"[")?;
+
+        let mut first = true;
         for elem in it {
             first = false;
-            tri!(ser::Serialize::serialize(&elem, &mut ser));
+            tri!(ser::Serialize::serialize(&elem, ser.value::<SeqSuffix>()));
         }
 
         if !first {
             // strip last comma
             unsafe {
-                self.writer.shrink(1);
+                ser.writer.shrink(1);
             }
         }
-        imap!(self.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
+        imap!(ser.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
     }
 }
 
 #[doc(hidden)]
-pub struct TupleSerializer<'w, W: BufWrite, S: Suffix> {
-    inner: Serializer<'w, W, SeqSuffix>,
+pub struct TupleSerializer<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> {
+    ser: &'a mut Serializer<'w, W, F>,
     first: bool,
     _suffix: PhantomData<S>,
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeTuple for TupleSerializer<'w, W, S> {
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> TupleSerializer<'a, 'w, W, F, S> {
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        if suffixed!() {
+            return value.serialize(self.ser.value::<SeqSuffix>());
+        }
+
+        let ser = &mut *self.ser;
+        ser.formatter.begin_array_value(ser.writer, self.first)?;
+        self.first = false;
+        tri!(value.serialize(ser.value::<RootSuffix>()));
+        imap!(ser.formatter.end_array_value(ser.writer))
+    }
+}
+
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeTuple
+    for TupleSerializer<'a, 'w, W, F, S>
+{
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut self.inner)
+        TupleSerializer::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        if likely!(!self.first) {
+        if !suffixed!() {
+            imap!(self.ser.formatter.end_array(self.ser.writer))
+        } else if likely!(!self.first) {
             unsafe {
-                self.inner.writer.shrink(SeqSuffix::SUFFIX.len());
+                self.ser.writer.shrink(SeqSuffix::SUFFIX.len());
             }
 
-            imap!(self.inner.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
+            imap!(self.ser.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
         } else {
             Ok(())
         }
     }
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeTupleStruct for TupleSerializer<'w, W, S> {
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeTupleStruct
+    for TupleSerializer<'a, 'w, W, F, S>
+{
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut self.inner)
+        TupleSerializer::serialize_element(self, value)
     }
 
     #[inline]
@@ -450,26 +878,29 @@ impl<'w, W: BufWrite, S: Suffix> ser::SerializeTupleStruct for TupleSerializer<'
     }
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeTupleVariant
-    for TupleSerializer<'w, W, S>
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeTupleVariant
+    for TupleSerializer<'a, 'w, W, F, S>
 {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut self.inner)
+        TupleSerializer::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        if likely!(!self.first) {
+        if !suffixed!() {
+            self.ser.formatter.end_array(self.ser.writer)?;
+            self.ser.end_variant()
+        } else if likely!(!self.first) {
             unsafe {
-                self.inner.writer.shrink(SeqSuffix::SUFFIX.len());
+                self.ser.writer.shrink(SeqSuffix::SUFFIX.len());
             }
 
-            imap!(self.inner.writer.write2(&RawStr("]}"), &RawStr(S::SUFFIX)))
+            imap!(self.ser.writer.write2(&RawStr("]}"), &RawStr(S::SUFFIX)))
         } else {
             Ok(())
         }
@@ -477,170 +908,276 @@ impl<'w, W: BufWrite, S: Suffix> ser::SerializeTupleVariant
 }
 
 #[doc(hidden)]
-pub struct SeqSerializer<'w, W: BufWrite, S: Suffix> {
-    inner: Serializer<'w, W, SeqSuffix>,
+pub struct SeqSerializer<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> {
+    ser: &'a mut Serializer<'w, W, F>,
     first: bool,
     _suffix: PhantomData<S>,
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeSeq for SeqSerializer<'w, W, S> {
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeSeq
+    for SeqSerializer<'a, 'w, W, F, S>
+{
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
+        if suffixed!() {
+            self.first = false;
+            return value.serialize(self.ser.value::<SeqSuffix>());
+        }
+
+        let ser = &mut *self.ser;
+        ser.formatter.begin_array_value(ser.writer, self.first)?;
         self.first = false;
-        value.serialize(&mut self.inner)
+        tri!(value.serialize(ser.value::<RootSuffix>()));
+        imap!(ser.formatter.end_array_value(ser.writer))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if !suffixed!() {
+            return imap!(self.ser.formatter.end_array(self.ser.writer));
+        }
+
         if likely!(!self.first) {
             unsafe {
-                self.inner.writer.shrink(SeqSuffix::SUFFIX.len());
+                self.ser.writer.shrink(SeqSuffix::SUFFIX.len());
             }
         }
 
-        imap!(self.inner.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
+        imap!(self.ser.writer.write2(&RawStr("]"), &RawStr(S::SUFFIX)))
     }
 }
 
 #[doc(hidden)]
-pub struct StructSerializer<'w, W: BufWrite, S: Suffix> {
-    inner: Serializer<'w, W, MapSuffix>,
+pub struct StructSerializer<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> {
+    ser: &'a mut Serializer<'w, W, F>,
     first: bool,
     _suffix: PhantomData<S>,
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeStruct for StructSerializer<'w, W, S> {
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeStruct
+    for StructSerializer<'a, 'w, W, F, S>
+{
     type Ok = ();
     type Error = Error;
 
     #[cfg_attr(not(debug_assertions), inline(always))]
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        if !need_escape(key) {
-            self.inner.writer.write2(&RawStr(key), &RawStr("\":"))?;
+        let ser = &mut *self.ser;
+        if !suffixed!() {
+            ser.formatter.begin_object_key(ser.writer, self.first)?;
+            self.first = false;
+            tri!(ser.write_static_str(key));
+            ser.formatter.end_object_key(ser.writer)?;
+            ser.formatter.begin_object_value(ser.writer)?;
+            tri!(value.serialize(ser.value::<RootSuffix>()));
+            return imap!(ser.formatter.end_object_value(ser.writer));
+        }
+
+        if !need_escape_with(key, ser.options.escaper()) {
+            ser.writer.write2(&RawStr(key), &RawStr("\":"))?;
         } else {
-            match escape_cold(self.inner.writer, key) {
+            match escape_cold(ser.writer, key, ser.options.escaper()) {
                 Ok(_) => {
-                    self.inner.writer.write_all(b"\":")?;
+                    ser.writer.write_all(b"\":")?;
                 }
                 Err(e) => return Err(Error::Io(e)),
             }
         }
 
-        value.serialize(&mut self.inner)
+        value.serialize(ser.value::<MapSuffix>())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if !suffixed!() {
+            return imap!(self.ser.formatter.end_object(self.ser.writer));
+        }
+
         unsafe {
             if likely!(MapSuffix::SUFFIX.len() > S::SUFFIX.len() && !self.first) {
-                let ptr = self.inner.writer.next_ptr();
+                let ptr = self.ser.writer.next_ptr();
                 *ptr.sub(MapSuffix::SUFFIX.len()) = b'}';
                 std::ptr::copy_nonoverlapping(
                     S::SUFFIX.as_ptr(),
                     ptr.sub(MapSuffix::SUFFIX.len() - 1),
                     S::SUFFIX.len(),
                 );
-                self.inner
+                self.ser
                     .writer
                     .shrink(MapSuffix::SUFFIX.len() - (S::SUFFIX.len() + 1));
                 Ok(())
             } else {
-                self.inner
+                self.ser
                     .writer
                     .shrink(MapSuffix::SUFFIX.len() - self.first as usize);
 
-                imap!(self.inner.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
+                imap!(self.ser.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
             }
         }
     }
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeStructVariant
-    for StructSerializer<'w, W, S>
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeStructVariant
+    for StructSerializer<'a, 'w, W, F, S>
 {
     type Ok = ();
     type Error = Error;
 
     #[cfg_attr(not(debug_assertions), inline(always))]
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         value: &T,
     ) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
         <Self as ser::SerializeStruct>::serialize_field(self, key, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if !suffixed!() {
+            self.ser.formatter.end_object(self.ser.writer)?;
+            return self.ser.end_variant();
+        }
+
         unsafe {
-            self.inner
+            self.ser
                 .writer
                 .shrink(MapSuffix::SUFFIX.len() - self.first as usize);
         }
 
-        imap!(self.inner.writer.write2(&RawStr("}}"), &RawStr(S::SUFFIX)))
+        imap!(self.ser.writer.write2(&RawStr("}}"), &RawStr(S::SUFFIX)))
     }
 }
 
 #[doc(hidden)]
-pub struct MapSerializer<'w, W: BufWrite, S: Suffix> {
-    inner: Serializer<'w, W, MapSuffix>,
+pub struct MapSerializer<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> {
+    ser: &'a mut Serializer<'w, W, F>,
     first: bool,
     _suffix: PhantomData<S>,
 }
 
-impl<'w, W: BufWrite, S: Suffix> ser::SerializeMap for MapSerializer<'w, W, S> {
+impl<'a, 'w, W: BufWrite, F: Formatter, S: Suffix> ser::SerializeMap
+    for MapSerializer<'a, 'w, W, F, S>
+{
     type Ok = ();
     type Error = Error;
 
     #[inline]
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
+        if suffixed!() {
+            self.first = false;
+            return key.serialize(MapKeySerializer {
+                ser: &mut *self.ser,
+            });
+        }
+
+        let ser = &mut *self.ser;
+        ser.formatter.begin_object_key(ser.writer, self.first)?;
         self.first = false;
-        key.serialize(MapKeySerializer {
-            writer: self.inner.writer,
-        })
+        tri!(key.serialize(MapKeySerializer { ser: &mut *ser }));
+        imap!(ser.formatter.end_object_key(ser.writer))
     }
 
     #[inline]
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ?Sized + ser::Serialize,
     {
-        value.serialize(&mut self.inner)
+        if suffixed!() {
+            return value.serialize(self.ser.value::<MapSuffix>());
+        }
+
+        let ser = &mut *self.ser;
+        ser.formatter.begin_object_value(ser.writer)?;
+        tri!(value.serialize(ser.value::<RootSuffix>()));
+        imap!(ser.formatter.end_object_value(ser.writer))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if !suffixed!() {
+            return imap!(self.ser.formatter.end_object(self.ser.writer));
+        }
+
         unsafe {
-            self.inner
+            self.ser
                 .writer
                 .shrink(MapSuffix::SUFFIX.len() - self.first as usize);
         }
 
-        imap!(self.inner.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
+        imap!(self.ser.writer.write2(&RawStr("}"), &RawStr(S::SUFFIX)))
     }
 }
 
-pub struct MapKeySerializer<'w, W: BufWrite> {
-    writer: &'w mut W,
+macro_rules! serialize_key_number {
+    ($($method:ident: $type:ty => $write:ident),*) => {
+        $(
+            fn $method(self, v: $type) -> Result<Self::Ok, Self::Error> {
+                let ser = self.ser;
+                if suffixed!() {
+                    imap!(ser.writer.write2(&v, &RawStr("\":")))
+                } else {
+                    ser.formatter.begin_string(ser.writer)?;
+                    ser.formatter.$write(ser.writer, v)?;
+                    imap!(ser.formatter.end_string(ser.writer))
+                }
+            }
+        )*
+    };
 }
 
-impl<'w, W: BufWrite> ser::Serializer for MapKeySerializer<'w, W> {
+/// Serializer for object keys
+///
+/// With [`CompactFormatter`], the opening quote has already been written as
+/// part of the previous separator, and the key is followed by `":`.
+#[doc(hidden)]
+pub struct MapKeySerializer<'a, 'w, W: BufWrite, F: Formatter> {
+    ser: &'a mut Serializer<'w, W, F>,
+}
+
+impl<'a, 'w, W: BufWrite, F: Formatter> MapKeySerializer<'a, 'w, W, F> {
+    #[cold]
+    fn stringify(self, value: &'static str) -> Result<(), Error> {
+        match self.ser.options.key_policy {
+            KeyPolicy::Strict => Err(Error::InvalidKey),
+            KeyPolicy::Stringify if suffixed!() => {
+                imap!(self.ser.writer.write2(&RawStr(value), &RawStr("\":")))
+            }
+            KeyPolicy::Stringify => self.ser.write_static_str(value),
+        }
+    }
+
+    #[cold]
+    fn serialize_non_finite(self, value: f64) -> Result<(), Error> {
+        match self.ser.options.non_finite {
+            NonFinitePolicy::String if suffixed!() => imap!(self
+                .ser
+                .writer
+                .write2(&RawStr(non_finite_str(value)), &RawStr("\":"))),
+            NonFinitePolicy::String => self.ser.write_static_str(non_finite_str(value)),
+            NonFinitePolicy::Null | NonFinitePolicy::Error => Err(Error::NonFiniteFloat),
+        }
+    }
+}
+
+impl<'a, 'w, W: BufWrite, F: Formatter> ser::Serializer
+    for MapKeySerializer<'a, 'w, W, F>
+{
     type Ok = ();
     type Error = Error;
 
@@ -652,113 +1189,146 @@ impl<'w, W: BufWrite> ser::Serializer for MapKeySerializer<'w, W> {
     type SerializeStruct = ser::Impossible<(), Error>;
     type SerializeStructVariant = ser::Impossible<(), Error>;
 
-    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
-    }
-
-    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
-
-    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
-    }
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.stringify(if v { "true" } else { "false" })
+    }
+
+    serialize_key_number!(
+        serialize_i8: i8 => write_i8,
+        serialize_i16: i16 => write_i16,
+        serialize_i32: i32 => write_i32,
+        serialize_i64: i64 => write_i64,
+        serialize_i128: i128 => write_i128,
+        serialize_u8: u8 => write_u8,
+        serialize_u16: u16 => write_u16,
+        serialize_u32: u32 => write_u32,
+        serialize_u64: u64 => write_u64,
+        serialize_u128: u128 => write_u128
+    );
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
+        let ser = self.ser;
+        if likely!(v.is_finite()) || ser.options.non_finite == NonFinitePolicy::Null {
+            if suffixed!() {
+                imap!(ser
+                    .writer
+                    .write2(&FormattedFloat(v, ser.options), &RawStr("\":")))
+            } else {
+                ser.formatter.begin_string(ser.writer)?;
+                tri!(ser.write_f32(v));
+                imap!(ser.formatter.end_string(ser.writer))
+            }
+        } else {
+            MapKeySerializer { ser }.serialize_non_finite(v as f64)
+        }
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        imap!(self.writer.write2(&v, &RawStr("\":")))
+        let ser = self.ser;
+        if likely!(v.is_finite()) || ser.options.non_finite == NonFinitePolicy::Null {
+            if suffixed!() {
+                imap!(ser
+                    .writer
+                    .write2(&FormattedFloat(v, ser.options), &RawStr("\":")))
+            } else {
+                ser.formatter.begin_string(ser.writer)?;
+                tri!(ser.write_f64(v));
+                imap!(ser.formatter.end_string(ser.writer))
+            }
+        } else {
+            MapKeySerializer { ser }.serialize_non_finite(v)
+        }
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        // TODO: pre-allocate all things
-        escape_char(self.writer, v)?;
-        imap!(self.writer.write_all(b"\":"))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(ser
+                .writer
+                .write2(&EscapedChar(v, ser.options.escaper()), &RawStr("\":")))
+        } else {
+            ser.formatter.begin_string(ser.writer)?;
+            escape_char(ser.writer, v, ser.options.escaper())?;
+            imap!(ser.formatter.end_string(ser.writer))
+        }
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        escape(self.writer, v)?;
-        imap!(self.writer.write_all(b"\":"))
+        let ser = self.ser;
+        if suffixed!() {
+            imap!(write_escaped(ser.writer, v, "\":", ser.options.escaper()))
+        } else {
+            ser.formatter.begin_string(ser.writer)?;
+            escape(ser.writer, v, ser.options.escaper())?;
+            imap!(ser.formatter.end_string(ser.writer))
+        }
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        let ser = self.ser;
+        match ser.options.bytes_encoding {
+            BytesEncoding::Array => Err(Error::InvalidKey),
+            encoding if suffixed!() => {
+                write_encoded(ser.writer, v, encoding)?;
+                imap!(ser.writer.write1(&RawStr("\":")))
+            }
+            encoding => {
+                ser.formatter.begin_string(ser.writer)?;
+                write_encoded(ser.writer, v, encoding)?;
+                imap!(ser.formatter.end_string(ser.writer))
+            }
+        }
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
+        self.stringify("null")
     }
 
-    fn serialize_some<T: ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        Err(Error::InvalidKey)
+        match self.ser.options.key_policy {
+            KeyPolicy::Strict => Err(Error::InvalidKey),
+            KeyPolicy::Stringify => value.serialize(self),
+        }
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
+        self.stringify("null")
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
+        self.stringify("null")
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Err(Error::InvalidKey)
+        match self.ser.options.key_policy {
+            KeyPolicy::Strict => Err(Error::InvalidKey),
+            KeyPolicy::Stringify => self.serialize_str(variant),
+        }
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        Err(Error::InvalidKey)
+        match self.ser.options.key_policy {
+            KeyPolicy::Strict => Err(Error::InvalidKey),
+            KeyPolicy::Stringify => value.serialize(self),
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -766,7 +1336,7 @@ impl<'w, W: BufWrite> ser::Serializer for MapKeySerializer<'w, W> {
         _value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         Err(Error::InvalidKey)
     }
@@ -0,0 +1,169 @@
+use crate::bufwrite::BufWrite;
+use crate::raw::RawStr;
+
+use std::io;
+
+macro_rules! default_write_number {
+    ($($method:ident: $type:ty),*) => {
+        $(
+            #[inline]
+            fn $method<W: BufWrite>(&mut self, writer: &mut W, value: $type) -> io::Result<()> {
+                writer.write1(&value)
+            }
+        )*
+    };
+}
+
+/// Hooks which control how each JSON token is written
+///
+/// All methods are statically dispatched and have default implementations
+/// which produce compact JSON, so a custom layout only has to override the
+/// methods it cares about.
+///
+/// ```
+/// use evil_json::ser::Formatter;
+/// use evil_json::Serializer;
+/// use serde::Serialize;
+/// use std::io;
+///
+/// /// Puts a space after each comma
+/// struct Spaced;
+///
+/// impl Formatter for Spaced {
+///     fn begin_array_value<W: evil_json::BufWrite>(
+///         &mut self,
+///         writer: &mut W,
+///         first: bool,
+///     ) -> io::Result<()> {
+///         if first {
+///             Ok(())
+///         } else {
+///             writer.write_all(b", ")
+///         }
+///     }
+/// }
+///
+/// let mut buf = Vec::new();
+/// [1, 2, 3]
+///     .serialize(&mut Serializer::with_formatter(&mut buf, Spaced))
+///     .unwrap();
+/// assert_eq!(buf, b"[1, 2, 3]");
+/// ```
+pub trait Formatter {
+    /// Set by [`CompactFormatter`], whose output the serializer writes
+    /// without calling any hook, so that each separator can be written
+    /// together with the value before it
+    #[doc(hidden)]
+    const COMPACT: bool = false;
+
+    #[inline]
+    fn write_null<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("null"))
+    }
+
+    #[inline]
+    fn write_bool<W: BufWrite>(&mut self, writer: &mut W, value: bool) -> io::Result<()> {
+        writer.write1(&value)
+    }
+
+    default_write_number!(
+        write_i8: i8,
+        write_i16: i16,
+        write_i32: i32,
+        write_i64: i64,
+        write_i128: i128,
+        write_u8: u8,
+        write_u16: u16,
+        write_u32: u32,
+        write_u64: u64,
+        write_u128: u128,
+        write_f32: f32,
+        write_f64: f64
+    );
+
+    /// Called before the escaped contents of a string or an object key
+    #[inline]
+    fn begin_string<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("\""))
+    }
+
+    /// Called after the escaped contents of a string or an object key
+    #[inline]
+    fn end_string<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("\""))
+    }
+
+    #[inline]
+    fn begin_array<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("["))
+    }
+
+    #[inline]
+    fn end_array<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("]"))
+    }
+
+    #[inline]
+    fn begin_array_value<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write1(&RawStr(","))
+        }
+    }
+
+    #[inline]
+    fn end_array_value<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn begin_object<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("{"))
+    }
+
+    #[inline]
+    fn end_object<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr("}"))
+    }
+
+    #[inline]
+    fn begin_object_key<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write1(&RawStr(","))
+        }
+    }
+
+    #[inline]
+    fn end_object_key<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    fn begin_object_value<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        writer.write1(&RawStr(":"))
+    }
+
+    #[inline]
+    fn end_object_value<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Formatter which writes JSON without any whitespace
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {
+    const COMPACT: bool = true;
+}
@@ -1 +1,8 @@
-pub(crate) mod compact;
+//! Serializer implementations
+
+pub mod compact;
+pub mod format;
+pub mod pretty;
+
+pub use self::format::{CompactFormatter, Formatter};
+pub use self::pretty::PrettyFormatter;
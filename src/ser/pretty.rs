@@ -0,0 +1,230 @@
+use crate::bufwrite::BufWrite;
+use crate::raw::RawStr;
+use crate::ser::compact;
+use crate::ser::format::Formatter;
+
+use std::borrow::Cow;
+use std::io;
+
+/// Line ending inserted between the elements of pretty-printed containers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    #[inline]
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+impl Default for LineEnding {
+    #[inline]
+    fn default() -> LineEnding {
+        LineEnding::Lf
+    }
+}
+
+/// Layout of the pretty-printed output
+///
+/// The default configuration indents with two spaces, separates lines with
+/// `\n` and puts a space after each colon, which is the same output as
+/// `serde_json::to_string_pretty`.
+#[derive(Clone, Debug)]
+pub struct PrettyConfig {
+    indent: Cow<'static, str>,
+    line_ending: LineEnding,
+    space_after_colon: bool,
+}
+
+impl PrettyConfig {
+    #[inline]
+    pub fn new() -> PrettyConfig {
+        PrettyConfig {
+            indent: Cow::Borrowed("  "),
+            line_ending: LineEnding::Lf,
+            space_after_colon: true,
+        }
+    }
+
+    /// String inserted once per nesting level at the beginning of each line
+    ///
+    /// The string is written as is, so it should only contain JSON whitespace.
+    #[inline]
+    pub fn indent<I: Into<Cow<'static, str>>>(mut self, indent: I) -> PrettyConfig {
+        self.indent = indent.into();
+        self
+    }
+
+    #[inline]
+    pub fn line_ending(mut self, line_ending: LineEnding) -> PrettyConfig {
+        self.line_ending = line_ending;
+        self
+    }
+
+    #[inline]
+    pub fn space_after_colon(mut self, space_after_colon: bool) -> PrettyConfig {
+        self.space_after_colon = space_after_colon;
+        self
+    }
+}
+
+impl Default for PrettyConfig {
+    #[inline]
+    fn default() -> PrettyConfig {
+        PrettyConfig::new()
+    }
+}
+
+/// Formatter which writes human-readable JSON according to [`PrettyConfig`]
+#[derive(Clone, Debug)]
+pub struct PrettyFormatter {
+    config: PrettyConfig,
+    depth: usize,
+    has_value: bool,
+}
+
+impl PrettyFormatter {
+    #[inline]
+    pub fn new() -> PrettyFormatter {
+        PrettyFormatter::with_config(PrettyConfig::new())
+    }
+
+    #[inline]
+    pub fn with_config(config: PrettyConfig) -> PrettyFormatter {
+        PrettyFormatter {
+            config,
+            depth: 0,
+            has_value: false,
+        }
+    }
+
+    fn write_indent<W: BufWrite>(&self, writer: &mut W) -> io::Result<()> {
+        tri!(writer.write_all(self.config.line_ending.as_str().as_bytes()));
+        for _ in 0..self.depth {
+            tri!(writer.write_all(self.config.indent.as_bytes()));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn begin_compound<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        open: &'static str,
+    ) -> io::Result<()> {
+        self.depth += 1;
+        self.has_value = false;
+        writer.write1(&RawStr(open))
+    }
+
+    #[inline]
+    fn begin_element<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if !first {
+            tri!(writer.write1(&RawStr(",")));
+        }
+        self.write_indent(writer)
+    }
+
+    #[inline]
+    fn end_compound<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        close: &'static str,
+    ) -> io::Result<()> {
+        self.depth -= 1;
+        if self.has_value {
+            tri!(self.write_indent(writer));
+        }
+        writer.write1(&RawStr(close))
+    }
+}
+
+impl Default for PrettyFormatter {
+    #[inline]
+    fn default() -> PrettyFormatter {
+        PrettyFormatter::new()
+    }
+}
+
+impl Formatter for PrettyFormatter {
+    #[inline]
+    fn begin_array<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin_compound(writer, "[")
+    }
+
+    #[inline]
+    fn end_array<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end_compound(writer, "]")
+    }
+
+    #[inline]
+    fn begin_array_value<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_element(writer, first)
+    }
+
+    #[inline]
+    fn end_array_value<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+
+    #[inline]
+    fn begin_object<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.begin_compound(writer, "{")
+    }
+
+    #[inline]
+    fn end_object<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.end_compound(writer, "}")
+    }
+
+    #[inline]
+    fn begin_object_key<W: BufWrite>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.begin_element(writer, first)
+    }
+
+    #[inline]
+    fn begin_object_value<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.config.space_after_colon {
+            writer.write1(&RawStr(": "))
+        } else {
+            writer.write1(&RawStr(":"))
+        }
+    }
+
+    #[inline]
+    fn end_object_value<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.has_value = true;
+        Ok(())
+    }
+}
+
+/// Serializer which writes human-readable JSON
+pub type Serializer<'w, W> = compact::Serializer<'w, W, PrettyFormatter>;
+
+impl<'w, W: BufWrite> compact::Serializer<'w, W, PrettyFormatter> {
+    #[inline]
+    pub fn with_config(writer: &'w mut W, config: PrettyConfig) -> Self {
+        Self::with_formatter(writer, PrettyFormatter::with_config(config))
+    }
+}
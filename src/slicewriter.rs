@@ -0,0 +1,234 @@
+use crate::bufwrite::{BufWrite, Scratch};
+use crate::suffix::MAX_SUFFIX_LEN;
+
+use core::cmp;
+use core::ptr;
+use std::io;
+
+/// `BufWrite` implementation over a fixed-size byte slice
+///
+/// Output which does not fit in the slice is discarded, but its length is
+/// still counted so that the caller can tell whether the whole document was
+/// stored.
+///
+/// Since `SerializeRaw::size_hint` is pessimistic, a reservation may exceed
+/// the remaining space even if the actual output fits. Such writes go to a
+/// window buffer which mirrors the output from `MAX_SUFFIX_LEN` bytes
+/// before the cursor, and are copied back to the slice afterwards. Keeping
+/// the last bytes in the window allows serializers to rewrite or shrink
+/// trailing separators as with other writers.
+pub(crate) struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    /// Total number of bytes written, which may exceed `buf.len()`
+    pos: usize,
+    window: Scratch,
+    /// Output position of `window[0]`, or `None` when writing to `buf`
+    ///
+    /// `pos <= buf.len()` always holds while this field is `None`.
+    window_base: Option<usize>,
+}
+
+impl<'a> SliceWriter<'a> {
+    #[inline]
+    pub fn new(buf: &'a mut [u8]) -> SliceWriter<'a> {
+        SliceWriter {
+            buf,
+            pos: 0,
+            window: Scratch::new(),
+            window_base: None,
+        }
+    }
+
+    /// Returns the number of bytes written so far, including discarded bytes
+    #[inline]
+    pub fn written(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns true if all output written so far fits in the slice
+    #[inline]
+    pub fn fits(&self) -> bool {
+        self.pos <= self.buf.len()
+    }
+
+    /// Stores the output which is still in the window into the slice
+    ///
+    /// Returns the output length, or `Err` with the required length if the
+    /// output does not fit in the slice.
+    #[inline]
+    pub fn finish(mut self) -> Result<usize, usize> {
+        self.commit_window();
+        if self.fits() {
+            Ok(self.written())
+        } else {
+            Err(self.written())
+        }
+    }
+
+    /// Starts writing to the window, keeping the last bytes addressable
+    #[cold]
+    fn enter_window(&mut self, additional: usize) {
+        let base = self.pos.saturating_sub(MAX_SUFFIX_LEN);
+        let end = cmp::min(self.pos, self.buf.len());
+        self.window.prepare(self.pos - base + additional);
+        if base < end {
+            self.window.as_mut_slice()[..end - base]
+                .copy_from_slice(&self.buf[base..end]);
+        }
+        self.window_base = Some(base);
+    }
+
+    /// Copies the part of the window which lies inside the slice back
+    #[cold]
+    fn commit_window(&mut self) {
+        if let Some(base) = self.window_base.take() {
+            let end = cmp::min(self.pos, self.buf.len());
+            if base < end {
+                self.buf[base..end]
+                    .copy_from_slice(&self.window.as_mut_slice()[..end - base]);
+            }
+        }
+    }
+}
+
+impl<'a> io::Write for SliceWriter<'a> {
+    #[inline]
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.write_all(data).map(|_| data.len())
+    }
+
+    #[inline]
+    fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        if likely!(self.window_base.is_none() && data.len() <= self.buf.len() - self.pos)
+        {
+            unsafe {
+                let dst = self.buf.as_mut_ptr().add(self.pos);
+                ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+            }
+            self.pos += data.len();
+            return Ok(());
+        }
+
+        self.commit_window();
+        if self.pos < self.buf.len() {
+            let n = cmp::min(self.buf.len() - self.pos, data.len());
+            self.buf[self.pos..self.pos + n].copy_from_slice(&data[..n]);
+        }
+        self.pos += data.len();
+        if self.pos > self.buf.len() {
+            self.enter_window(0);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl<'a> BufWrite for SliceWriter<'a> {
+    const KEEPS_TAIL: bool = true;
+
+    #[inline]
+    fn next_ptr(&mut self) -> *mut u8 {
+        match self.window_base {
+            None => unsafe { self.buf.as_mut_ptr().add(self.pos) },
+            Some(base) => unsafe {
+                self.window.as_mut_slice().as_mut_ptr().add(self.pos - base)
+            },
+        }
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) -> Result<(), io::Error> {
+        match self.window_base {
+            None if likely!(additional <= self.buf.len() - self.pos) => return Ok(()),
+            Some(base)
+                if self.pos - base + additional <= self.window.as_mut_slice().len() =>
+            {
+                return Ok(())
+            }
+            _ => {}
+        }
+
+        self.commit_window();
+        if !(self.pos <= self.buf.len() && additional <= self.buf.len() - self.pos) {
+            self.enter_window(additional);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn advance(&mut self, additional: usize) {
+        self.pos += additional;
+    }
+
+    #[inline]
+    unsafe fn shrink(&mut self, shrink_size: usize) {
+        debug_assert!(shrink_size <= self.pos - self.window_base.unwrap_or(0));
+        self.pos -= shrink_size;
+    }
+
+    #[inline]
+    fn max_reserve(&self) -> usize {
+        // larger reservations near the end of the slice would allocate window
+        cmp::max(self.buf.len().saturating_sub(self.pos), Scratch::INLINE_SIZE / 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SliceWriter;
+    use crate::bufwrite::BufWrite;
+    use std::io::Write;
+
+    #[test]
+    fn overflow() {
+        let mut buf = [0u8; 4];
+        let mut writer = SliceWriter::new(&mut buf);
+
+        writer.write_all(b"ab").unwrap();
+        writer.write1(&345u32).unwrap();
+        assert_eq!(writer.written(), 5);
+        assert!(!writer.fits());
+
+        unsafe { writer.shrink(1) };
+        assert_eq!(writer.written(), 4);
+        assert!(writer.fits());
+        assert_eq!(writer.finish(), Ok(4));
+        assert_eq!(&buf, b"ab34");
+    }
+
+    #[test]
+    fn write_after_overflow() {
+        let mut buf = [0u8; 4];
+        let mut writer = SliceWriter::new(&mut buf);
+
+        writer.write1(&12345u32).unwrap();
+        assert!(!writer.fits());
+        assert_eq!(writer.written(), 5);
+
+        writer.reserve(8).unwrap();
+        writer.write_all(b"67").unwrap();
+        writer.write1(&89u8).unwrap();
+        unsafe { writer.shrink(3) };
+        assert_eq!(writer.written(), 6);
+        assert_eq!(writer.finish(), Err(6));
+        assert_eq!(&buf, b"1234");
+    }
+
+    #[test]
+    fn reserve() {
+        let mut buf = [0u8; 2];
+        let mut writer = SliceWriter::new(&mut buf);
+
+        assert_eq!(writer.reserve(64).ok(), Some(()));
+        assert_eq!(writer.reserve(1000).ok(), Some(()));
+
+        writer.write1(&7u64).unwrap();
+        writer.write1(&8u64).unwrap();
+        assert_eq!(writer.finish(), Ok(2));
+        assert_eq!(&buf, b"78");
+    }
+}
@@ -1,3 +1,11 @@
+/// Upper bound of `Suffix::SUFFIX.len()`
+///
+/// The writers which set `BufWrite::KEEPS_TAIL` keep at least this many
+/// trailing bytes in memory when they flush, because a separator written
+/// after the last element is removed again with `BufWrite::shrink` when the
+/// container is closed.
+pub const MAX_SUFFIX_LEN: usize = 2;
+
 pub trait Suffix {
     const SUFFIX: &'static str;
 }
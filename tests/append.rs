@@ -0,0 +1,77 @@
+use evil_json::Error;
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+
+#[test]
+fn to_vec_into() {
+    let mut buf = Vec::with_capacity(64);
+    evil_json::to_vec_into(&mut buf, &[1, 2]).unwrap();
+    assert_eq!(buf, b"[1,2]");
+
+    let ptr = buf.as_ptr();
+    buf.push(b'\n');
+    evil_json::to_vec_into(&mut buf, "a\"b").unwrap();
+    assert_eq!(buf, b"[1,2]\n\"a\\\"b\"");
+    assert_eq!(buf.as_ptr(), ptr);
+}
+
+#[test]
+fn to_string_into() {
+    let mut buf = String::from("data: ");
+    evil_json::to_string_into(&mut buf, &Some('x')).unwrap();
+    assert_eq!(buf, r#"data: "x""#);
+
+    buf.clear();
+    evil_json::to_string_into(&mut buf, &()).unwrap();
+    assert_eq!(buf, "null");
+}
+
+#[test]
+fn error() {
+    let mut map = BTreeMap::new();
+    map.insert("ok", BTreeMap::new());
+    map.get_mut("ok").unwrap().insert(vec![1], "invalid key");
+
+    let mut buf = b"prefix".to_vec();
+    assert!(matches!(
+        evil_json::to_vec_into(&mut buf, &map),
+        Err(Error::InvalidKey)
+    ));
+    assert_eq!(buf, b"prefix");
+
+    let mut buf = String::from("prefix");
+    assert!(matches!(
+        evil_json::to_string_into(&mut buf, &map),
+        Err(Error::InvalidKey)
+    ));
+    assert_eq!(buf, "prefix");
+}
+
+/// Writes part of an array before panicking
+struct Panics;
+
+impl Serialize for Panics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        seq.serialize_element("partial")?;
+        panic!("serialize panicked");
+    }
+}
+
+#[test]
+fn panic() {
+    let mut buf = b"prefix".to_vec();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        evil_json::to_vec_into(&mut buf, &[Panics])
+    }));
+    assert!(res.is_err());
+    assert_eq!(buf, b"prefix");
+
+    let mut buf = String::from("prefix");
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        evil_json::to_string_into(&mut buf, &[Panics])
+    }));
+    assert!(res.is_err());
+    assert_eq!(buf, "prefix");
+}
@@ -0,0 +1,168 @@
+use evil_json::{BufWrite, BufWriter};
+use serde::Serialize;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+
+/// Delegates to `Vec<u8>`, but never exposes more than `reserved` bytes
+struct Strict {
+    buf: Vec<u8>,
+    reserved: usize,
+}
+
+impl io::Write for Strict {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.reserved = 0;
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl BufWrite for Strict {
+    fn next_ptr(&mut self) -> *mut u8 {
+        self.buf.next_ptr()
+    }
+
+    fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        self.reserved = additional;
+        self.buf.reserve_exact(additional);
+        Ok(())
+    }
+
+    unsafe fn advance(&mut self, additional: usize) {
+        assert!(additional <= self.reserved);
+        self.reserved = 0;
+        self.buf.advance(additional);
+    }
+
+    unsafe fn shrink(&mut self, shrink_size: usize) {
+        assert!(shrink_size <= 2);
+        self.buf.shrink(shrink_size);
+    }
+}
+
+/// Sends committed bytes on right away, as unbuffered sinks do
+struct Eager {
+    scratch: Vec<u8>,
+    sent: Vec<u8>,
+}
+
+impl io::Write for Eager {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.sent.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+unsafe impl BufWrite for Eager {
+    fn next_ptr(&mut self) -> *mut u8 {
+        self.scratch.as_mut_ptr()
+    }
+
+    fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        self.scratch.reserve(additional);
+        Ok(())
+    }
+
+    unsafe fn advance(&mut self, additional: usize) {
+        self.scratch.set_len(additional);
+        self.sent.extend_from_slice(&self.scratch);
+        self.scratch.clear();
+    }
+
+    unsafe fn shrink(&mut self, _shrink_size: usize) {
+        panic!("output which has been sent cannot be removed");
+    }
+}
+
+#[derive(Serialize)]
+struct LongNames {
+    a_field_name_which_is_much_longer_than_the_reservations_for_numbers_or_literals: u8,
+    #[serde(
+        rename = "another\tescaped field name which is also longer than the usual values"
+    )]
+    escaped: Vec<Variant>,
+}
+
+#[derive(Serialize)]
+enum Variant {
+    AUnitVariantNameWhichIsMuchLongerThanTheReservationsForNumbersOrLiterals,
+    ANewtypeVariantNameWhichIsMuchLongerThanTheReservationsForNumbersOrLiterals(i8),
+}
+
+fn long_names() -> LongNames {
+    LongNames {
+        a_field_name_which_is_much_longer_than_the_reservations_for_numbers_or_literals: 1,
+        escaped: vec![
+            Variant::AUnitVariantNameWhichIsMuchLongerThanTheReservationsForNumbersOrLiterals,
+            Variant::ANewtypeVariantNameWhichIsMuchLongerThanTheReservationsForNumbersOrLiterals(
+                -1,
+            ),
+        ],
+    }
+}
+
+#[test]
+fn custom() {
+    fn check<T: Serialize + ?Sized>(value: &T) {
+        let mut writer = Strict {
+            buf: Vec::new(),
+            reserved: 0,
+        };
+        evil_json::to_buf_write(&mut writer, value).unwrap();
+        assert_eq!(writer.buf, evil_json::to_vec(value).unwrap());
+    }
+
+    check(&(1u8, -2i64, 0.5f64, "\u{0}\u{1}", 'c', ()));
+    check(&vec![Some(vec!["x"; 3]); 3]);
+    check(&long_names());
+}
+
+#[test]
+fn eager() {
+    fn check<T: Serialize + ?Sized>(value: &T) {
+        let mut writer = Eager {
+            scratch: Vec::new(),
+            sent: Vec::new(),
+        };
+        evil_json::to_buf_write(&mut writer, value).unwrap();
+        assert_eq!(writer.sent, evil_json::to_vec(value).unwrap());
+    }
+
+    check(&(1u8, -2i64, 0.5f64, "\u{0}\u{1}", 'c', ()));
+    check(&vec![Some(vec!["x"; 3]); 3]);
+    check(&[f64::NAN, 1.5]);
+    let map: BTreeMap<_, _> = vec![(-1, 'k'), (2, 'v')].into_iter().collect();
+    check(&map);
+    check(&long_names());
+}
+
+#[test]
+fn long_names_small_buffers() {
+    let value = long_names();
+    let expected = evil_json::to_vec(&value).unwrap();
+
+    let mut out = Vec::new();
+    {
+        let mut writer = BufWriter::with_capacity(0, &mut out);
+        evil_json::to_buf_write(&mut writer, &value).unwrap();
+    }
+    assert_eq!(out, expected);
+
+    assert_eq!(evil_json::serialized_len(&value).unwrap(), expected.len());
+
+    let mut buf = vec![0u8; expected.len()];
+    assert_eq!(
+        evil_json::to_slice(&mut buf, &value).unwrap(),
+        expected.len()
+    );
+    assert_eq!(buf, expected);
+}
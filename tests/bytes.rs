@@ -0,0 +1,248 @@
+use evil_json::{BytesEncoding, Error, Options, SerializerBuilder};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+fn encoding(encoding: BytesEncoding) -> Options {
+    SerializerBuilder::new().bytes_encoding(encoding).build()
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T, options: &Options) -> String {
+    evil_json::to_string_with(value, options).unwrap()
+}
+
+/// Straightforward encoder to compare against
+fn reference(bytes: &[u8], encoding: BytesEncoding) -> String {
+    let (alphabet, pad): (&[u8], _) = match encoding {
+        BytesEncoding::Base64 => (
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+            true,
+        ),
+        BytesEncoding::Base64UrlNoPad => (
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+            false,
+        ),
+        BytesEncoding::HexLower => {
+            return bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+        BytesEncoding::HexUpper => {
+            return bytes.iter().map(|b| format!("{:02X}", b)).collect()
+        }
+        BytesEncoding::Array => unreachable!(),
+    };
+
+    let mut out = String::new();
+    for group in bytes.chunks(3) {
+        let mut n = 0u32;
+        for (i, &b) in group.iter().enumerate() {
+            n |= (b as u32) << (16 - 8 * i);
+        }
+        for i in 0..=group.len() {
+            out.push(alphabet[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+        }
+        if pad {
+            for _ in group.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+const ENCODINGS: [BytesEncoding; 4] = [
+    BytesEncoding::Base64,
+    BytesEncoding::Base64UrlNoPad,
+    BytesEncoding::HexLower,
+    BytesEncoding::HexUpper,
+];
+
+#[test]
+fn default() {
+    assert_eq!(Options::default().bytes_encoding(), BytesEncoding::Array);
+    assert_eq!(to_json(&Bytes(b"\x01\xff"), &Options::default()), "[1,255]");
+    assert_eq!(to_json(&Bytes(b""), &Options::default()), "[]");
+}
+
+#[test]
+fn examples() {
+    let bytes = Bytes(b"\xfb\xff\xbf?");
+    assert_eq!(
+        to_json(&bytes, &encoding(BytesEncoding::Base64)),
+        r#""+/+/Pw==""#
+    );
+    assert_eq!(
+        to_json(&bytes, &encoding(BytesEncoding::Base64UrlNoPad)),
+        r#""-_-_Pw""#
+    );
+    assert_eq!(
+        to_json(&bytes, &encoding(BytesEncoding::HexLower)),
+        r#""fbffbf3f""#
+    );
+    assert_eq!(
+        to_json(&bytes, &encoding(BytesEncoding::HexUpper)),
+        r#""FBFFBF3F""#
+    );
+    assert_eq!(
+        to_json(
+            &(Bytes(b"foo"), Bytes(b"")),
+            &encoding(BytesEncoding::Base64)
+        ),
+        r#"["Zm9v",""]"#
+    );
+}
+
+#[test]
+fn lengths() {
+    let data = (0..100u8).map(|i| i.wrapping_mul(97)).collect::<Vec<_>>();
+    for &enc in &ENCODINGS {
+        let options = encoding(enc);
+        assert_eq!(options.bytes_encoding(), enc);
+        for len in 0..data.len() {
+            let bytes = &data[..len];
+            assert_eq!(
+                to_json(&[Bytes(bytes)], &options),
+                format!("[\"{}\"]", reference(bytes, enc)),
+                "{:?} {}",
+                enc,
+                len
+            );
+        }
+    }
+}
+
+#[test]
+fn long() {
+    // longer than a single reservation of every writer
+    let data = (0..100_000u32)
+        .map(|i| ((i * 7919) >> 3) as u8)
+        .collect::<Vec<_>>();
+    for &enc in &ENCODINGS {
+        let options = encoding(enc);
+        let expected = format!("\"{}\"", reference(&data, enc));
+        assert_eq!(to_json(&Bytes(&data), &options), expected);
+
+        let mut out = Vec::new();
+        evil_json::to_writer_with(&mut out, &Bytes(&data), &options).unwrap();
+        assert_eq!(out, expected.as_bytes());
+    }
+}
+
+#[test]
+fn pretty() {
+    let options = encoding(BytesEncoding::HexLower);
+    let mut buf = Vec::new();
+    vec![Bytes(b"\x12\x34")]
+        .serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+            &mut buf,
+            Default::default(),
+            &options,
+        ))
+        .unwrap();
+    assert_eq!(buf, b"[\n  \"1234\"\n]");
+}
+
+#[test]
+fn map_keys() {
+    let mut map = BTreeMap::new();
+    map.insert(Bytes(b"ab"), Bytes(b"\xff"));
+    map.insert(Bytes(b"c"), Bytes(b""));
+    assert!(matches!(
+        evil_json::to_vec_with(&map, &Options::default()),
+        Err(Error::InvalidKey)
+    ));
+    assert_eq!(
+        to_json(&map, &encoding(BytesEncoding::Base64)),
+        r#"{"YWI=":"/w==","Yw==":""}"#
+    );
+
+    let mut buf = Vec::new();
+    map.serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+        &mut buf,
+        Default::default(),
+        &encoding(BytesEncoding::HexUpper),
+    ))
+    .unwrap();
+    assert_eq!(buf, b"{\n  \"6162\": \"FF\",\n  \"63\": \"\"\n}");
+}
+
+/// Numbers joined the way `Serialize for [u8]` writes them element by element
+fn array(bytes: &[u8]) -> String {
+    let items = bytes.iter().map(u8::to_string).collect::<Vec<_>>();
+    format!("[{}]", items.join(","))
+}
+
+#[test]
+fn array_every_byte() {
+    let all = (0..=255u8).collect::<Vec<_>>();
+    let expected = array(&all);
+    assert_eq!(evil_json::to_string(&all).unwrap(), expected);
+    assert_eq!(evil_json::to_string(&all[..]).unwrap(), expected);
+    assert_eq!(to_json(&Bytes(&all), &Options::default()), expected);
+}
+
+#[test]
+fn array_suffixes() {
+    let value = (
+        vec![0u8, 9, 10, 99, 100, 255],
+        Vec::<u8>::new(),
+        Bytes(b"\x07"),
+    );
+    assert_eq!(
+        evil_json::to_string(&value).unwrap(),
+        "[[0,9,10,99,100,255],[],[7]]"
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![1u8, 2]);
+    map.insert("b", vec![]);
+    assert_eq!(evil_json::to_string(&map).unwrap(), r#"{"a":[1,2],"b":[]}"#);
+}
+
+#[test]
+fn array_writers() {
+    for &len in &[0, 1, 22, 23, 24, 25, 100, 1000, 10_000] {
+        let data = (0..len).map(|i| (i * 31) as u8).collect::<Vec<_>>();
+        let value = vec![data.clone(), data];
+        let expected = format!("[{0},{0}]", array(&value[0]));
+        assert_eq!(evil_json::to_string(&value).unwrap(), expected);
+
+        let mut out = Vec::new();
+        {
+            let mut writer = evil_json::BufWriter::with_capacity(0, &mut out);
+            evil_json::to_buf_write(&mut writer, &value).unwrap();
+        }
+        assert_eq!(out, expected.as_bytes());
+
+        assert_eq!(evil_json::serialized_len(&value).unwrap(), expected.len());
+
+        let mut buf = vec![0u8; expected.len()];
+        assert_eq!(
+            evil_json::to_slice(&mut buf, &value).unwrap(),
+            expected.len()
+        );
+        assert_eq!(buf, expected.as_bytes());
+    }
+}
+
+#[test]
+fn array_iterators() {
+    // only slices take the fast path, other iterators of bytes still work
+    let deque = (250..=255u8).collect::<std::collections::VecDeque<_>>();
+    assert_eq!(
+        evil_json::to_string(&deque).unwrap(),
+        "[250,251,252,253,254,255]"
+    );
+    let mut buf = Vec::new();
+    evil_json::Serializer::new(&mut buf)
+        .collect_seq([3u8, 30].iter().copied())
+        .unwrap();
+    assert_eq!(buf, b"[3,30]");
+}
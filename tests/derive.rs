@@ -18,7 +18,7 @@ struct NewTypeStruct(i32);
 
 #[test]
 fn new_type_struct() {
-    assert_eq!(to_json(&NewTypeStruct(i32::min_value())), "-2147483648");
+    assert_eq!(to_json(&NewTypeStruct(i32::MIN)), "-2147483648");
 }
 
 #[derive(Serialize)]
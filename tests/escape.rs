@@ -83,3 +83,28 @@ fn random() {
         }
     }
 }
+
+#[test]
+#[cfg(not(miri))]
+fn chunked() {
+    let mut rng = SmallRng::seed_from_u64(0x5d2f6c7a1e3b9048);
+
+    // around the length where the worst case no longer fits in a single
+    // reservation
+    for &len in &[600, 682, 683, 700, 1400, 10000, 20000] {
+        let bytes: Vec<u8> = (0..len).map(|_| rng.gen_range(0..0x80)).collect();
+        let feed = std::str::from_utf8(bytes.as_slice()).unwrap();
+        let expected = naive_escape(feed);
+
+        assert_eq!(to_json(feed), expected);
+        assert_eq!(to_json(&[feed, feed]), format!("[{},{}]", expected, expected));
+
+        let mut out = Vec::new();
+        evil_json::to_writer(&mut out, &(feed, 'x')).unwrap();
+        assert_eq!(out, format!("[{},\"x\"]", expected).as_bytes());
+
+        let mut map = std::collections::BTreeMap::new();
+        map.insert(feed, '\u{1}');
+        assert_eq!(to_json(&map), format!("{{{}:\"\\u0001\"}}", expected));
+    }
+}
@@ -0,0 +1,404 @@
+use evil_json::{FloatFormat, NonFinitePolicy, Options, SerializerBuilder};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+fn format(format: FloatFormat) -> SerializerBuilder {
+    SerializerBuilder::new().float_format(format)
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T, options: &Options) -> String {
+    evil_json::to_string_with(value, options).unwrap()
+}
+
+/// Random finite floats spread over the whole exponent range
+fn random_floats(count: usize) -> Vec<f64> {
+    let mut rng = SmallRng::seed_from_u64(24);
+    (0..count)
+        .map(|_| loop {
+            let value = f64::from_bits(rng.gen());
+            if value.is_finite() {
+                break value;
+            }
+        })
+        .collect()
+}
+
+/// Digits written by `FloatFormat::Shortest` and the exponent of the last
+/// one, e.g. `(true, [1, 2, 5], -9)` for `-1.25e-7`
+fn shortest<T: Serialize>(value: T) -> (bool, Vec<u8>, i32) {
+    let json = evil_json::to_string(&value).unwrap();
+    let (significand, exponent) = match json.find('e') {
+        Some(e) => (&json[..e], json[e + 1..].parse::<i32>().unwrap()),
+        None => (&json[..], 0),
+    };
+    let fraction = significand
+        .find('.')
+        .map_or(0, |p| significand.len() - p - 1);
+    let mut digits = significand
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|b| b - b'0')
+        .collect::<Vec<_>>();
+    while digits.len() > 1 && digits[0] == 0 {
+        digits.remove(0);
+    }
+    (json.starts_with('-'), digits, exponent - fraction as i32)
+}
+
+/// Keeps the first `keep` digits, rounding to nearest, ties to even
+fn round(digits: &[u8], keep: i32) -> Vec<u8> {
+    if keep < 0 {
+        return vec![];
+    }
+    let keep = keep as usize;
+    let mut kept = digits[..keep].to_vec();
+    let dropped = &digits[keep..];
+    let up = match dropped.split_first() {
+        Some((&first, rest)) => {
+            first > 5
+                || first == 5
+                    && (rest.iter().any(|&d| d != 0)
+                        || kept.last().map_or(false, |&d| d % 2 == 1))
+        }
+        None => false,
+    };
+    if up {
+        match kept.iter().rposition(|&d| d != 9) {
+            Some(i) => {
+                kept[i] += 1;
+                kept.truncate(i + 1);
+                kept.resize(keep, 0);
+            }
+            None => {
+                kept = vec![1];
+                kept.resize(keep + 1, 0);
+            }
+        }
+    }
+    kept
+}
+
+/// Straightforward fixed formatting of the shortest digits to compare against
+fn reference_fixed<T: Serialize>(value: T, decimals: usize) -> String {
+    let (negative, digits, exponent) = shortest(value);
+    let shift = exponent + decimals as i32;
+    let mut scaled = if shift >= 0 {
+        let mut scaled = digits;
+        scaled.resize(scaled.len() + shift as usize, 0);
+        scaled
+    } else {
+        round(&digits, digits.len() as i32 + shift)
+    };
+    while scaled.len() > decimals + 1 && scaled[0] == 0 {
+        scaled.remove(0);
+    }
+    while scaled.len() < decimals + 1 {
+        scaled.insert(0, 0);
+    }
+
+    let mut out = if negative {
+        "-".to_owned()
+    } else {
+        String::new()
+    };
+    let point = scaled.len() - decimals;
+    for (i, d) in scaled.into_iter().enumerate() {
+        if i == point {
+            out.push('.');
+        }
+        out.push((b'0' + d) as char);
+    }
+    out
+}
+
+/// The shortest digits rounded to `n` significant digits
+fn reference_significant<T: Serialize>(value: T, n: usize) -> f64 {
+    let (negative, digits, exponent) = shortest(value);
+    let keep = std::cmp::min(n, digits.len());
+    let rounded = round(&digits, keep as i32);
+    let exponent = exponent + (digits.len() - keep) as i32;
+    let digits = rounded
+        .iter()
+        .map(|d| (b'0' + d) as char)
+        .collect::<String>();
+    let sign = if negative { "-" } else { "" };
+    format!("{}{}e{}", sign, digits, exponent).parse().unwrap()
+}
+
+#[test]
+fn default() {
+    let options = Options::default();
+    assert_eq!(options.float_format(), FloatFormat::Shortest);
+    assert!(!options.trim_integral_floats());
+    assert!(!options.normalize_negative_zero());
+    assert_eq!(
+        to_json(&(1.0f64, -0.0f64, 0.1f32, 1e16f64), &options),
+        "[1.0,-0.0,0.1,1e16]"
+    );
+}
+
+#[test]
+fn trim_integral() {
+    let options = SerializerBuilder::new().trim_integral_floats(true).build();
+    assert!(options.trim_integral_floats());
+    assert_eq!(
+        to_json(
+            &(1.0f64, 100.0f32, 0.5f64, -3.0f64, 1e16f64, 1e-7f64),
+            &options
+        ),
+        "[1,100,0.5,-3,1e16,1e-7]"
+    );
+}
+
+#[test]
+fn negative_zero() {
+    let options = SerializerBuilder::new()
+        .normalize_negative_zero(true)
+        .build();
+    assert!(options.normalize_negative_zero());
+    assert_eq!(
+        to_json(&(-0.0f64, -0.0f32, -1.0f64), &options),
+        "[0.0,0.0,-1.0]"
+    );
+
+    let options = format(FloatFormat::Fixed(2))
+        .normalize_negative_zero(true)
+        .build();
+    assert_eq!(
+        to_json(&(-0.001f64, -0.0f64, -0.01f64), &options),
+        "[0.00,0.00,-0.01]"
+    );
+
+    let options = format(FloatFormat::Fixed(2)).build();
+    assert_eq!(to_json(&(-0.001f64, -0.0f64), &options), "[-0.00,-0.00]");
+}
+
+#[test]
+fn fixed() {
+    let options = format(FloatFormat::Fixed(2)).build();
+    assert_eq!(options.float_format(), FloatFormat::Fixed(2));
+    assert_eq!(
+        to_json(
+            &(3.14259f64, 1.0f64, 2.675f64, 0.125f64, -9.999f32),
+            &options
+        ),
+        "[3.14,1.00,2.68,0.12,-10.00]"
+    );
+    assert_eq!(
+        to_json(&1e300f64, &options),
+        format!("1{}.00", "0".repeat(300))
+    );
+
+    // each type is rounded from its own shortest digits
+    let options = format(FloatFormat::Fixed(10)).build();
+    assert_eq!(
+        to_json(&(0.1f32, 0.1f64, 16777217.0f32), &options),
+        "[0.1000000000,0.1000000000,16777216.0000000000]"
+    );
+
+    let options = format(FloatFormat::Fixed(0)).build();
+    assert_eq!(to_json(&(2.5f64, 3.5f64, -0.4f64), &options), "[2,4,-0]");
+
+    let options = format(FloatFormat::Fixed(3))
+        .trim_integral_floats(true)
+        .build();
+    assert_eq!(
+        to_json(&(1.0f64, 1.0001f64, 1.5f64, 1e20f64), &options),
+        "[1,1.000,1.500,100000000000000000000]"
+    );
+    let options = format(FloatFormat::Fixed(2))
+        .trim_integral_floats(true)
+        .build();
+    assert_eq!(
+        to_json(&(1.001f64, 0.999f32, -2.0f32), &options),
+        "[1.00,1.00,-2]"
+    );
+
+    for value in random_floats(1000) {
+        for &decimals in &[0u8, 1, 6, 20] {
+            let options = format(FloatFormat::Fixed(decimals)).build();
+            assert_eq!(
+                to_json(&value, &options),
+                reference_fixed(value, decimals as usize)
+            );
+            if (value as f32).is_finite() {
+                assert_eq!(
+                    to_json(&(value as f32), &options),
+                    reference_fixed(value as f32, decimals as usize)
+                );
+            }
+        }
+    }
+    for &value in &[9.995f64, 0.0005, 99.5, 0.05, 123456.7890123] {
+        for decimals in 0..=12u8 {
+            let options = format(FloatFormat::Fixed(decimals)).build();
+            assert_eq!(
+                to_json(&value, &options),
+                reference_fixed(value, decimals as usize)
+            );
+        }
+    }
+}
+
+#[test]
+fn shortest_digits() {
+    // halfway in the shortest digits, but not in the exact binary values
+    let values = (2.675f64, 1.005f64, 1.015f64, 2.675f32);
+    assert_eq!(
+        format!("{:.2} {:.2} {:.2}", values.0, values.1, values.2),
+        "2.67 1.00 1.01"
+    );
+
+    let options = format(FloatFormat::Fixed(2)).build();
+    assert_eq!(to_json(&values, &options), "[2.68,1.00,1.02,2.68]");
+
+    let options = format(FloatFormat::Significant(3)).build();
+    assert_eq!(to_json(&values, &options), "[2.68,1.0,1.02,2.68]");
+}
+
+#[test]
+fn significant() {
+    let options = format(FloatFormat::Significant(3)).build();
+    assert_eq!(options.float_format(), FloatFormat::Significant(3));
+    assert_eq!(
+        to_json(
+            &(
+                3.14259f64,
+                1.0f64,
+                1234.5f64,
+                0.000123456f64,
+                1.5e20f64,
+                -2.5e-7f32
+            ),
+            &options
+        ),
+        "[3.14,1.0,1230.0,0.000123,1.5e20,-2.5e-7]"
+    );
+    assert_eq!(
+        to_json(&(0.0f64, -0.0f64, 999.5f64), &options),
+        "[0.0,-0.0,1000.0]"
+    );
+
+    let options = format(FloatFormat::Significant(0)).build();
+    assert_eq!(to_json(&(123.0f64, 0.56f64), &options), "[100.0,0.6]");
+
+    let options = format(FloatFormat::Significant(2))
+        .trim_integral_floats(true)
+        .normalize_negative_zero(true)
+        .build();
+    assert_eq!(
+        to_json(&(12.0f64, 123.0f64, 1.25f64, -0.0f64), &options),
+        "[12,120,1.2,0]"
+    );
+    assert_eq!(to_json(&(1.96f64, 0.1f32), &options), "[2.0,0.1]");
+
+    // coordinates from GeoJSON payloads such as canada.json
+    let options = format(FloatFormat::Significant(7)).build();
+    assert_eq!(
+        to_json(&[-65.61361699999998f64, 43.42027300000001], &options),
+        "[-65.61362,43.42027]"
+    );
+}
+
+#[test]
+fn significant_round_trip() {
+    let max = format(FloatFormat::Significant(17)).build();
+    let clamped = format(FloatFormat::Significant(255)).build();
+    let six = format(FloatFormat::Significant(6)).build();
+    for value in random_floats(10_000) {
+        let json = to_json(&value, &max);
+        assert_eq!(json.parse::<f64>().unwrap(), value, "{}", json);
+        assert_eq!(to_json(&value, &clamped), json);
+
+        let json = to_json(&value, &six);
+        let expected = reference_significant(value, 6);
+        assert_eq!(json.parse::<f64>().unwrap(), expected, "{}", json);
+
+        let value = value as f32;
+        if !value.is_finite() {
+            continue;
+        }
+        let json = to_json(&value, &max);
+        assert_eq!(json.parse::<f32>().unwrap(), value, "{}", json);
+        let json = to_json(&value, &six);
+        let expected = reference_significant(value, 6);
+        assert_eq!(json.parse::<f64>().unwrap(), expected, "{}", json);
+    }
+}
+
+#[test]
+fn non_finite() {
+    let options = format(FloatFormat::Fixed(2)).build();
+    assert_eq!(to_json(&(f64::NAN, f32::INFINITY), &options), "[null,null]");
+
+    let options = format(FloatFormat::Significant(2))
+        .non_finite(NonFinitePolicy::String)
+        .build();
+    assert_eq!(
+        to_json(&(f64::NEG_INFINITY, 1.0f64), &options),
+        r#"["-Infinity",1.0]"#
+    );
+}
+
+#[test]
+fn writers() {
+    let options = format(FloatFormat::Fixed(100)).build();
+    let value = vec![1e300f64, -1.5, 0.1];
+    let expected = format!(
+        "[{},{},{}]",
+        reference_fixed(1e300f64, 100),
+        reference_fixed(-1.5f64, 100),
+        reference_fixed(0.1f64, 100)
+    );
+    assert_eq!(to_json(&value, &options), expected);
+
+    let mut out = Vec::new();
+    evil_json::to_writer_with(&mut out, &value, &options).unwrap();
+    assert_eq!(out, expected.as_bytes());
+}
+
+#[test]
+fn pretty_and_keys() {
+    struct Map(Vec<(f64, f32)>);
+
+    impl Serialize for Map {
+        fn serialize<S: serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+
+    let options = format(FloatFormat::Fixed(1))
+        .trim_integral_floats(true)
+        .build();
+    let map = Map(vec![(1.0, 2.25), (0.25, -0.0)]);
+    assert_eq!(to_json(&map, &options), r#"{"1":2.2,"0.2":-0}"#);
+
+    let mut buf = Vec::new();
+    map.serialize(
+        &mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+            &mut buf,
+            Default::default(),
+            &options,
+        ),
+    )
+    .unwrap();
+    assert_eq!(buf, b"{\n  \"1\": 2.2,\n  \"0.2\": -0\n}");
+
+    let mut map = BTreeMap::new();
+    map.insert("x", 1.0f64);
+    let mut buf = Vec::new();
+    map.serialize(
+        &mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+            &mut buf,
+            Default::default(),
+            &SerializerBuilder::new().trim_integral_floats(true).build(),
+        ),
+    )
+    .unwrap();
+    assert_eq!(buf, b"{\n  \"x\": 1\n}");
+}
@@ -0,0 +1,188 @@
+use evil_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+use evil_json::{
+    BufWrite, FloatFormat, KeyPolicy, NonFinitePolicy, Serializer, SerializerBuilder,
+};
+use serde::Serialize;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+
+#[derive(Serialize)]
+struct Record {
+    id: u64,
+    #[serde(rename = "na\"me")]
+    name: &'static str,
+    tags: Vec<char>,
+    nested: Option<Box<Record>>,
+    kind: Kind,
+}
+
+#[derive(Serialize)]
+enum Kind {
+    Unit,
+    Newtype(i8),
+    Tuple(u8, f32),
+    Struct { a: () },
+    EmptyTuple(),
+    EmptyStruct {},
+}
+
+fn record() -> Record {
+    Record {
+        id: 1,
+        name: "outer\n",
+        tags: vec!['a', '"'],
+        nested: Some(Box::new(Record {
+            id: u64::MAX,
+            name: "",
+            tags: vec![],
+            nested: None,
+            kind: Kind::Tuple(3, 0.5),
+        })),
+        kind: Kind::Struct { a: () },
+    }
+}
+
+fn format<T: Serialize + ?Sized, F: Formatter>(value: &T, formatter: F) -> String {
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::with_formatter(&mut buf, formatter))
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+/// Keeps the default hooks, which the serializer calls for every token
+struct DefaultHooks;
+
+impl Formatter for DefaultHooks {}
+
+#[test]
+fn compact_formatter() {
+    fn check<T: Serialize + ?Sized>(value: &T) {
+        let mut buf = Vec::new();
+        value
+            .serialize(&mut Serializer::<_, CompactFormatter>::new(&mut buf))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            evil_json::to_string(value).unwrap()
+        );
+        assert_eq!(
+            format(value, CompactFormatter),
+            evil_json::to_string(value).unwrap()
+        );
+        assert_eq!(
+            format(value, DefaultHooks),
+            evil_json::to_string(value).unwrap()
+        );
+    }
+
+    check(&record());
+    check(&[
+        Kind::Unit,
+        Kind::Newtype(-1),
+        Kind::EmptyTuple(),
+        Kind::EmptyStruct {},
+    ]);
+    check(&Vec::<Vec<u8>>::new());
+    check(&vec![vec![], vec![1u8]]);
+
+    let mut map = BTreeMap::new();
+    map.insert(-5i32, BTreeMap::<&str, ()>::new());
+    map.insert(7, vec![("k", ())].into_iter().collect());
+    check(&map);
+}
+
+#[test]
+fn default_hooks_with_options() {
+    let options = SerializerBuilder::new()
+        .key_policy(KeyPolicy::Stringify)
+        .non_finite(NonFinitePolicy::String)
+        .float_format(FloatFormat::Fixed(2))
+        .build();
+
+    let mut map = BTreeMap::new();
+    map.insert(Some(true), (f64::NAN, 'x'));
+    map.insert(None, (1.0 / 3.0, '"'));
+
+    let mut compact = Vec::new();
+    map.serialize(&mut Serializer::with_options(&mut compact, &options))
+        .unwrap();
+    let mut hooks = Vec::new();
+    map.serialize(&mut Serializer::with_formatter_and_options(
+        &mut hooks,
+        DefaultHooks,
+        &options,
+    ))
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(compact).unwrap(),
+        r#"{"null":[0.33,"\""],"true":["NaN","x"]}"#
+    );
+    assert_eq!(
+        String::from_utf8(hooks).unwrap(),
+        r#"{"null":[0.33,"\""],"true":["NaN","x"]}"#
+    );
+}
+
+#[test]
+fn pretty_formatter() {
+    assert_eq!(
+        format(&record(), PrettyFormatter::new()),
+        evil_json::to_string_pretty(&record()).unwrap()
+    );
+}
+
+/// Writes 64-bit integers as strings so that JavaScript can read them exactly
+struct QuotedIntegers;
+
+impl Formatter for QuotedIntegers {
+    fn write_u64<W: BufWrite>(&mut self, writer: &mut W, value: u64) -> io::Result<()> {
+        writer.write_all(b"\"")?;
+        CompactFormatter.write_u64(writer, value)?;
+        writer.write_all(b"\"")
+    }
+}
+
+#[test]
+fn custom_formatter() {
+    assert_eq!(format(&[1u64, 2], QuotedIntegers), r#"["1","2"]"#);
+    assert_eq!(format(&(1u32, 1u64), QuotedIntegers), r#"[1,"1"]"#);
+}
+
+/// Counts the structural tokens passed to the formatter
+#[derive(Default)]
+struct Counter {
+    arrays: usize,
+    objects: usize,
+    keys: usize,
+}
+
+impl Formatter for Counter {
+    fn begin_array<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.arrays += 1;
+        writer.write_all(b"[")
+    }
+
+    fn begin_object<W: BufWrite>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.objects += 1;
+        writer.write_all(b"{")
+    }
+
+    fn end_object_key<W: BufWrite>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.keys += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn stateful_formatter() {
+    let mut buf = Vec::new();
+    let mut ser = Serializer::with_formatter(&mut buf, Counter::default());
+    record().serialize(&mut ser).unwrap();
+
+    let counter = ser.into_formatter();
+    assert_eq!(counter.arrays, 3);
+    assert_eq!(counter.objects, 5);
+    assert_eq!(counter.keys, 13);
+}
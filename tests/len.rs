@@ -0,0 +1,69 @@
+use serde::Serialize;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+struct Record {
+    id: i64,
+    text: String,
+    ratio: f32,
+    flags: (bool, Option<()>),
+    children: Vec<Record>,
+}
+
+fn record(depth: usize) -> Record {
+    Record {
+        id: -(depth as i64) * 1_000_003,
+        text: "\u{0}\u{1f}\\\"\t\u{e9}".repeat(depth * 11),
+        ratio: 1.0 / (depth as f32 + 3.0),
+        flags: (depth % 2 == 0, None),
+        children: (0..depth).map(record).collect(),
+    }
+}
+
+fn check<T: Serialize + ?Sized>(value: &T) {
+    assert_eq!(
+        evil_json::serialized_len(value).unwrap(),
+        evil_json::to_vec(value).unwrap().len()
+    );
+}
+
+#[test]
+fn scalar() {
+    check(&());
+    check(&i128::MIN);
+    check(&u8::MAX);
+    check(&f64::MIN_POSITIVE);
+    check(&f32::NAN);
+    check(&'\u{8}');
+    check("");
+}
+
+#[test]
+fn string() {
+    check("plain ascii");
+    check("\u{7f}\u{80}\u{10ffff}");
+    check(&"\n\"\\\u{1}".repeat(100));
+    check(&"x".repeat(10000));
+}
+
+#[test]
+fn nested() {
+    check(&record(5));
+    check(&vec![Vec::<u8>::new(); 3]);
+
+    let mut map = BTreeMap::new();
+    map.insert("a\u{0}", record(2));
+    map.insert("b", record(0));
+    check(&map);
+}
+
+#[test]
+fn error() {
+    let mut map = BTreeMap::new();
+    map.insert(vec![0], 0);
+    assert!(matches!(
+        evil_json::serialized_len(&map),
+        Err(evil_json::Error::InvalidKey)
+    ));
+}
@@ -28,7 +28,7 @@ fn int_key() {
         r#"{"-1":"-3","5":"15"}"#
     );
     assert_eq!(
-        to_json(&btreemap! {i128::max_value()=>()}),
+        to_json(&btreemap! {i128::MAX=>()}),
         r#"{"170141183460469231731687303715884105727":null}"#
     );
     assert_eq!(
@@ -0,0 +1,472 @@
+use evil_json::{
+    Error, EscapeProfile, EscapeTable, KeyPolicy, NonAsciiPolicy, NonFinitePolicy,
+    Options, Serializer, SerializerBuilder,
+};
+use serde::Serialize;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Id(u32);
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+enum Color {
+    Red,
+    #[serde(rename = "G\treen")]
+    Green,
+    #[serde(rename = "Bl\u{fc}e")]
+    Blue,
+}
+
+#[derive(Serialize)]
+struct Emoji {
+    #[serde(rename = "\u{1f600}")]
+    smile: char,
+}
+
+fn stringify() -> Options {
+    SerializerBuilder::new()
+        .key_policy(KeyPolicy::Stringify)
+        .build()
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T, options: &Options) -> String {
+    evil_json::to_string_with(value, options).unwrap()
+}
+
+#[test]
+fn default() {
+    let options = Options::default();
+    assert_eq!(options.key_policy(), KeyPolicy::Strict);
+    assert_eq!(options.non_ascii(), NonAsciiPolicy::Keep);
+    assert_eq!(
+        SerializerBuilder::new().build().key_policy(),
+        KeyPolicy::Strict
+    );
+    assert_eq!(to_json(&(1, "a"), &options), r#"[1,"a"]"#);
+}
+
+#[test]
+fn strict_keys() {
+    let mut map = BTreeMap::new();
+    map.insert(false, 0);
+    assert!(matches!(evil_json::to_vec(&map), Err(Error::InvalidKey)));
+    assert!(matches!(
+        evil_json::to_vec_with(&map, &Options::default()),
+        Err(Error::InvalidKey)
+    ));
+
+    let mut map = BTreeMap::new();
+    map.insert(Id(1), 0);
+    assert!(matches!(evil_json::to_vec(&map), Err(Error::InvalidKey)));
+}
+
+#[test]
+fn stringify_keys() {
+    let options = stringify();
+
+    let mut map = BTreeMap::new();
+    map.insert(false, 0);
+    map.insert(true, 1);
+    assert_eq!(to_json(&map, &options), r#"{"false":0,"true":1}"#);
+
+    let mut map = BTreeMap::new();
+    map.insert((), "unit");
+    assert_eq!(to_json(&map, &options), r#"{"null":"unit"}"#);
+
+    let mut map = BTreeMap::new();
+    map.insert(None, 0);
+    map.insert(Some('x'), 1);
+    assert_eq!(to_json(&map, &options), r#"{"null":0,"x":1}"#);
+
+    let mut map = BTreeMap::new();
+    map.insert(Id(7), vec![Id(8)]);
+    assert_eq!(to_json(&map, &options), r#"{"7":[8]}"#);
+
+    let mut map = BTreeMap::new();
+    map.insert(Color::Red, 1);
+    map.insert(Color::Green, 2);
+    assert_eq!(to_json(&map, &options), r#"{"Red":1,"G\treen":2}"#);
+}
+
+#[test]
+fn stringify_keys_pretty() {
+    let options = stringify();
+    let mut map = BTreeMap::new();
+    map.insert(true, ());
+
+    let mut buf = Vec::new();
+    map.serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+        &mut buf,
+        Default::default(),
+        &options,
+    ))
+    .unwrap();
+    assert_eq!(buf, b"{\n  \"true\": null\n}");
+}
+
+#[test]
+fn serializer() {
+    let options = stringify();
+    let mut buf = b"prefix:".to_vec();
+    let mut ser = Serializer::with_options(&mut buf, &options);
+    assert_eq!(ser.options().key_policy(), KeyPolicy::Stringify);
+
+    let mut map = BTreeMap::new();
+    map.insert(true, [1.5]);
+    map.serialize(&mut ser).unwrap();
+    assert_eq!(buf, br#"prefix:{"true":[1.5]}"#);
+}
+
+#[test]
+fn writer() {
+    let mut map = BTreeMap::new();
+    map.insert(true, "yes");
+
+    let mut out = Vec::new();
+    evil_json::to_writer_with(&mut out, &map, &stringify()).unwrap();
+    assert_eq!(out, br#"{"true":"yes"}"#);
+
+    assert!(matches!(
+        evil_json::to_writer_with(&mut out, &map, &Options::default()),
+        Err(Error::InvalidKey)
+    ));
+}
+
+fn non_ascii(policy: NonAsciiPolicy) -> Options {
+    SerializerBuilder::new().non_ascii(policy).build()
+}
+
+#[test]
+fn escape_non_ascii() {
+    let options = non_ascii(NonAsciiPolicy::Escape);
+    assert_eq!(options.non_ascii(), NonAsciiPolicy::Escape);
+
+    assert_eq!(to_json("abc", &options), r#""abc""#);
+    assert_eq!(
+        to_json("\u{e9}t\u{e9}\n\u{3042}\u{1f600}", &options),
+        r#""\u00e9t\u00e9\n\u3042\ud83d\ude00""#
+    );
+    assert_eq!(
+        to_json(
+            &['~', '\u{80}', '\u{ffff}', '\u{10000}', '\u{10ffff}'],
+            &options
+        ),
+        r#"["~","\u0080","\uffff","\ud800\udc00","\udbff\udfff"]"#
+    );
+    assert_eq!(to_json(&Color::Blue, &options), r#""Bl\u00fce""#);
+    assert_eq!(
+        to_json(&Emoji { smile: '\u{1f642}' }, &options),
+        r#"{"\ud83d\ude00":"\ud83d\ude42"}"#
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("\u{3042}", '\u{3044}');
+    assert_eq!(to_json(&map, &options), r#"{"\u3042":"\u3044"}"#);
+
+    // long strings are escaped in chunks by the writer
+    let long = "\u{1f600}x".repeat(1000);
+    let mut out = Vec::new();
+    evil_json::to_writer_with(&mut out, &long, &options).unwrap();
+    assert_eq!(
+        out,
+        format!("\"{}\"", r#"\ud83d\ude00x"#.repeat(1000)).as_bytes()
+    );
+}
+
+#[test]
+fn escape_non_bmp() {
+    let options = non_ascii(NonAsciiPolicy::EscapeNonBmp);
+    assert_eq!(
+        to_json("\u{e9}\u{3042}\u{ffff}\u{10000}\"", &options),
+        "\"\u{e9}\u{3042}\u{ffff}\\ud800\\udc00\\\"\""
+    );
+    assert_eq!(to_json(&Color::Blue, &options), "\"Bl\u{fc}e\"");
+    assert_eq!(
+        to_json(&Emoji { smile: '\u{e9}' }, &options),
+        "{\"\\ud83d\\ude00\":\"\u{e9}\"}"
+    );
+}
+
+#[test]
+fn escape_non_ascii_pretty() {
+    let options = non_ascii(NonAsciiPolicy::Escape);
+    let mut map = BTreeMap::new();
+    map.insert('\u{3042}', vec!["\u{1f600}"]);
+
+    let mut buf = Vec::new();
+    map.serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+        &mut buf,
+        Default::default(),
+        &options,
+    ))
+    .unwrap();
+    assert_eq!(buf, b"{\n  \"\\u3042\": [\n    \"\\ud83d\\ude00\"\n  ]\n}");
+}
+
+#[derive(Serialize)]
+struct Tag {
+    #[serde(rename = "<b>")]
+    bold: &'static str,
+}
+
+#[test]
+fn html_safe() {
+    let options = SerializerBuilder::new()
+        .escape_profile(EscapeProfile::HtmlSafe)
+        .build();
+    assert_eq!(options.escape_profile(), EscapeProfile::HtmlSafe);
+
+    assert_eq!(
+        to_json("</script><!-- a & 'b' -->", &options),
+        r#""\u003c/script\u003e\u003c!-- a \u0026 \u0027b\u0027 --\u003e""#
+    );
+    assert_eq!(
+        to_json("\u{2027}\u{2028}\u{2029}\u{3042}\n", &options),
+        "\"\u{2027}\\u2028\\u2029\u{3042}\\n\""
+    );
+    assert_eq!(
+        to_json(&['<', '\u{2028}', '\u{e9}'], &options),
+        "[\"\\u003c\",\"\\u2028\",\"\u{e9}\"]"
+    );
+    assert_eq!(
+        to_json(&Tag { bold: "&amp;" }, &options),
+        r#"{"\u003cb\u003e":"\u0026amp;"}"#
+    );
+    assert_eq!(Options::default().escape_profile(), EscapeProfile::Json);
+    assert_eq!(
+        to_json(&Tag { bold: "&" }, &Options::default()),
+        r#"{"<b>":"&"}"#
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("a>b", "\u{2029}");
+    assert_eq!(to_json(&map, &options), r#"{"a\u003eb":"\u2029"}"#);
+}
+
+#[test]
+fn html_safe_ascii() {
+    let options = SerializerBuilder::new()
+        .escape_profile(EscapeProfile::HtmlSafe)
+        .non_ascii(NonAsciiPolicy::Escape)
+        .build();
+    assert_eq!(
+        to_json("<\u{2028}\u{e9}>", &options),
+        r#""\u003c\u2028\u00e9\u003e""#
+    );
+}
+
+#[derive(Serialize)]
+enum Path {
+    #[serde(rename = "a/b")]
+    Slash { dir: &'static str },
+}
+
+#[test]
+fn custom_table() {
+    let table = EscapeTable::new()
+        .escape(b'/', *b"\\/")
+        .escape(0x7F, *b"\\u")
+        .uppercase_hex(true);
+    let options = SerializerBuilder::new().escape_table(table).build();
+    assert_eq!(
+        options.escape_table().unwrap().entries()[b'/' as usize],
+        *b"\\/"
+    );
+    assert!(Options::default().escape_table().is_none());
+
+    assert_eq!(
+        to_json("http://example.com/\u{7f}\u{1b}\u{e9}", &options),
+        "\"http:\\/\\/example.com\\/\\u007F\\u001B\u{e9}\""
+    );
+    assert_eq!(to_json(&['/', '\u{1f}'], &options), r#"["\/","\u001F"]"#);
+    assert_eq!(
+        to_json(&Path::Slash { dir: "/usr" }, &options),
+        r#"{"a\/b":{"dir":"\/usr"}}"#
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("/", '/');
+    assert_eq!(to_json(&map, &options), r#"{"\/":"\/"}"#);
+
+    // the table is combined with the other settings
+    let options = SerializerBuilder::new()
+        .escape_table(EscapeTable::new().uppercase_hex(true))
+        .non_ascii(NonAsciiPolicy::Escape)
+        .escape_profile(EscapeProfile::HtmlSafe)
+        .build();
+    assert_eq!(
+        to_json("<\u{e9}\u{1f600}/", &options),
+        r#""\u003C\u00E9\uD83D\uDE00/""#
+    );
+}
+
+#[test]
+fn custom_table_from_array() {
+    let mut entries = *EscapeTable::new().entries();
+    entries[b'\'' as usize] = *b"\\u";
+    let options = SerializerBuilder::new()
+        .escape_table(EscapeTable::from_array(entries))
+        .build();
+    assert_eq!(to_json("it's", &options), r#""it\u0027s""#);
+}
+
+#[test]
+#[should_panic]
+fn custom_table_unescaped_control() {
+    let _ = EscapeTable::new().escape(b'\n', [0, 0]);
+}
+
+#[test]
+#[should_panic]
+fn custom_table_invalid_sequence() {
+    let _ = EscapeTable::new().escape(b'a', *b"\\a");
+}
+
+#[test]
+#[should_panic]
+fn custom_table_mismatched_sequence() {
+    let _ = EscapeTable::new().escape(b'\t', *b"\\n");
+}
+
+#[test]
+#[should_panic]
+fn custom_table_non_ascii() {
+    let _ = EscapeTable::new().escape(0xE9, *b"\\u");
+}
+
+fn non_finite(policy: NonFinitePolicy) -> Options {
+    SerializerBuilder::new().non_finite(policy).build()
+}
+
+#[derive(Serialize)]
+struct Sample {
+    min: f32,
+    max: f64,
+}
+
+fn pretty_json<T: serde::Serialize + ?Sized>(value: &T, options: &Options) -> String {
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+            &mut buf,
+            Default::default(),
+            options,
+        ))
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn non_finite_null() {
+    let options = Options::default();
+    assert_eq!(options.non_finite(), NonFinitePolicy::Null);
+    assert_eq!(
+        to_json(&(f64::NAN, f32::NEG_INFINITY, 1.5f64), &options),
+        "[null,null,1.5]"
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("x", f64::INFINITY);
+    assert_eq!(
+        pretty_json(&map, &non_finite(NonFinitePolicy::Null)),
+        "{\n  \"x\": null\n}"
+    );
+}
+
+#[test]
+fn non_finite_error() {
+    let options = non_finite(NonFinitePolicy::Error);
+    assert_eq!(to_json(&[0.5f64, -0.0], &options), "[0.5,-0.0]");
+    for value in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        assert!(matches!(
+            evil_json::to_vec_with(&[*value], &options),
+            Err(Error::NonFiniteFloat)
+        ));
+        assert!(matches!(
+            evil_json::to_vec_with(&(*value as f32), &options),
+            Err(Error::NonFiniteFloat)
+        ));
+    }
+
+    let value = Sample {
+        min: 0.0,
+        max: f64::NAN,
+    };
+    let mut buf = Vec::new();
+    let result = value.serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+        &mut buf,
+        Default::default(),
+        &options,
+    ));
+    assert!(matches!(result, Err(Error::NonFiniteFloat)));
+    assert_eq!(
+        Error::NonFiniteFloat.to_string(),
+        "Non-finite float is not allowed in JSON"
+    );
+}
+
+#[test]
+fn non_finite_string() {
+    let options = non_finite(NonFinitePolicy::String);
+    assert_eq!(
+        to_json(
+            &(f64::NAN, f64::INFINITY, f64::NEG_INFINITY, f32::NAN, 2.0f32),
+            &options
+        ),
+        r#"["NaN","Infinity","-Infinity","NaN",2.0]"#
+    );
+    let value = Sample {
+        min: f32::NEG_INFINITY,
+        max: f64::INFINITY,
+    };
+    assert_eq!(
+        to_json(&value, &options),
+        r#"{"min":"-Infinity","max":"Infinity"}"#
+    );
+    assert_eq!(
+        pretty_json(&value, &options),
+        "{\n  \"min\": \"-Infinity\",\n  \"max\": \"Infinity\"\n}"
+    );
+}
+
+#[test]
+fn non_finite_keys() {
+    // floats are not `Ord`, so the keys go through a slice of pairs
+    struct Map(Vec<(f64, u8)>);
+
+    impl Serialize for Map {
+        fn serialize<S: serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+
+    let map = Map(vec![(1.5, 0), (f64::NAN, 1), (f64::NEG_INFINITY, 2)]);
+    assert_eq!(
+        to_json(&map, &Options::default()),
+        r#"{"1.5":0,"null":1,"null":2}"#
+    );
+    assert_eq!(
+        to_json(&map, &non_finite(NonFinitePolicy::String)),
+        r#"{"1.5":0,"NaN":1,"-Infinity":2}"#
+    );
+    assert_eq!(
+        pretty_json(&map, &non_finite(NonFinitePolicy::String)),
+        "{\n  \"1.5\": 0,\n  \"NaN\": 1,\n  \"-Infinity\": 2\n}"
+    );
+    assert!(matches!(
+        evil_json::to_vec_with(&map, &non_finite(NonFinitePolicy::Error)),
+        Err(Error::NonFiniteFloat)
+    ));
+
+    let mut buf = Vec::new();
+    let result = map.serialize(&mut evil_json::ser::pretty::Serializer::with_formatter_and_options(
+        &mut buf,
+        Default::default(),
+        &non_finite(NonFinitePolicy::Error),
+    ));
+    assert!(matches!(result, Err(Error::NonFiniteFloat)));
+}
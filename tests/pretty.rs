@@ -0,0 +1,148 @@
+use evil_json::ser::pretty::{LineEnding, PrettyConfig, Serializer};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    evil_json::to_string_pretty(value).unwrap()
+}
+
+fn to_json_with<T: serde::Serialize + ?Sized>(value: &T, config: PrettyConfig) -> String {
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::with_config(&mut buf, config))
+        .unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[derive(Serialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Empty {}
+
+#[derive(Serialize)]
+enum Shape {
+    Unit,
+    Circle(f64),
+    Line(Point, Point),
+    Rect { w: u8, h: u8 },
+    NoFields(),
+}
+
+#[test]
+fn scalar() {
+    assert_eq!(to_json(&()), "null");
+    assert_eq!(to_json(&-3i8), "-3");
+    assert_eq!(to_json(&"a\"b"), r#""a\"b""#);
+    assert_eq!(to_json(&'\n'), r#""\n""#);
+    assert_eq!(to_json(&Shape::Unit), r#""Unit""#);
+}
+
+#[test]
+fn empty() {
+    assert_eq!(to_json::<[u8; 0]>(&[]), "[]");
+    assert_eq!(to_json(&Vec::<u8>::new()), "[]");
+    assert_eq!(to_json(&BTreeMap::<u8, u8>::new()), "{}");
+    assert_eq!(to_json(&Empty {}), "{}");
+    assert_eq!(to_json(&Shape::NoFields()), "{\n  \"NoFields\": []\n}");
+}
+
+#[test]
+fn seq() {
+    assert_eq!(to_json(&[1, 2, 3]), "[\n  1,\n  2,\n  3\n]");
+    assert_eq!(
+        to_json(&vec![vec![1], vec![]]),
+        "[\n  [\n    1\n  ],\n  []\n]"
+    );
+    assert_eq!(to_json(&(true, "x")), "[\n  true,\n  \"x\"\n]");
+}
+
+#[test]
+fn map() {
+    let mut map = BTreeMap::new();
+    map.insert("b\tc", vec![Point { x: 1, y: -1 }]);
+    map.insert("a", vec![]);
+    assert_eq!(
+        to_json(&map),
+        r#"{
+  "a": [],
+  "b\tc": [
+    {
+      "x": 1,
+      "y": -1
+    }
+  ]
+}"#
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert(10u32, 0.5f32);
+    assert_eq!(to_json(&map), "{\n  \"10\": 0.5\n}");
+}
+
+#[test]
+fn variant() {
+    assert_eq!(to_json(&Shape::Circle(1.5)), "{\n  \"Circle\": 1.5\n}");
+    assert_eq!(
+        to_json(&Shape::Line(Point { x: 0, y: 0 }, Point { x: 1, y: 2 })),
+        r#"{
+  "Line": [
+    {
+      "x": 0,
+      "y": 0
+    },
+    {
+      "x": 1,
+      "y": 2
+    }
+  ]
+}"#
+    );
+    assert_eq!(
+        to_json(&Shape::Rect { w: 3, h: 4 }),
+        "{\n  \"Rect\": {\n    \"w\": 3,\n    \"h\": 4\n  }\n}"
+    );
+}
+
+#[test]
+fn config() {
+    let value = vec![Point { x: 5, y: 6 }];
+
+    assert_eq!(
+        to_json_with(&value, PrettyConfig::new().indent("\t")),
+        "[\n\t{\n\t\t\"x\": 5,\n\t\t\"y\": 6\n\t}\n]"
+    );
+    assert_eq!(
+        to_json_with(&value, PrettyConfig::new().line_ending(LineEnding::CrLf)),
+        "[\r\n  {\r\n    \"x\": 5,\r\n    \"y\": 6\r\n  }\r\n]"
+    );
+    assert_eq!(
+        to_json_with(
+            &value,
+            PrettyConfig::new()
+                .indent(String::from(" "))
+                .space_after_colon(false)
+        ),
+        "[\n {\n  \"x\":5,\n  \"y\":6\n }\n]"
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert('k', ());
+    assert_eq!(
+        to_json_with(&map, PrettyConfig::new().space_after_colon(false)),
+        "{\n  \"k\":null\n}"
+    );
+}
+
+#[test]
+fn writer() {
+    let mut out = Vec::new();
+    evil_json::to_writer_pretty(&mut out, &[Point { x: 7, y: 8 }]).unwrap();
+    assert_eq!(
+        out,
+        evil_json::to_vec_pretty(&[Point { x: 7, y: 8 }]).unwrap()
+    );
+}
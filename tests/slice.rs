@@ -0,0 +1,99 @@
+use evil_json::Error;
+use serde::Serialize;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+struct Item {
+    id: u64,
+    name: String,
+    score: f64,
+    tags: Vec<&'static str>,
+}
+
+fn items() -> Vec<Item> {
+    (0..8)
+        .map(|i| Item {
+            id: u64::MAX >> i,
+            name: "\u{3042}\"\n".repeat(i as usize * 7),
+            score: -1.25 * i as f64,
+            tags: vec!["a"; i as usize % 3],
+        })
+        .collect()
+}
+
+/// Checks every buffer length from zero to one more than the output
+fn check<T: Serialize + ?Sized>(value: &T) {
+    let expected = evil_json::to_vec(value).unwrap();
+
+    for len in 0..=expected.len() + 1 {
+        let mut buf = vec![0u8; len];
+        match evil_json::to_slice(&mut buf, value) {
+            Ok(written) => {
+                assert!(len >= expected.len());
+                assert_eq!(&buf[..written], &expected[..]);
+            }
+            Err(Error::BufferTooSmall { required }) => {
+                assert!(len < expected.len());
+                assert_eq!(required, Some(expected.len()));
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+}
+
+#[test]
+fn exact_fit() {
+    let value = items();
+    let expected = evil_json::to_vec(&value).unwrap();
+
+    let mut buf = vec![0u8; expected.len()];
+    assert_eq!(evil_json::to_slice(&mut buf, &value).unwrap(), expected.len());
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn overflow() {
+    let mut buf = [0u8; 4];
+    match evil_json::to_slice(&mut buf, &[1, 2, 3]) {
+        Err(Error::BufferTooSmall { required }) => assert_eq!(required, Some(7)),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    assert!(matches!(
+        evil_json::to_slice(&mut [], &()),
+        Err(Error::BufferTooSmall { required: Some(4) })
+    ));
+}
+
+#[test]
+fn boundaries() {
+    check(&0u8);
+    check(&u64::MAX);
+    check(&-0.5f32);
+    check("escape\t\"\\");
+    check(&'\u{1f600}');
+    check(&items());
+    check(&vec![vec![Item {
+        id: 0,
+        name: String::new(),
+        score: 0.0,
+        tags: vec![],
+    }]]);
+
+    let mut map = BTreeMap::new();
+    map.insert("key\u{7f}", items());
+    map.insert("", Vec::new());
+    check(&map);
+}
+
+#[test]
+fn other_error() {
+    let mut map = BTreeMap::new();
+    map.insert((), ());
+    let mut buf = [0u8; 64];
+    assert!(matches!(
+        evil_json::to_slice(&mut buf, &map),
+        Err(Error::InvalidKey)
+    ));
+}
@@ -12,19 +12,20 @@ fn null() {
 fn integer() {
     assert_eq!(to_json(&false), "false");
     assert_eq!(to_json(&0u32), "0");
-    assert_eq!(to_json(&u16::max_value()), "65535");
+    assert_eq!(to_json(&u16::MAX), "65535");
     assert_eq!(to_json(&-81_isize), "-81");
     assert_eq!(
-        to_json(&u128::max_value()),
+        to_json(&u128::MAX),
         "340282366920938463463374607431768211455"
     );
     assert_eq!(
-        to_json(&i128::min_value()),
+        to_json(&i128::MIN),
         "-170141183460469231731687303715884105728"
     );
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn float() {
     assert_eq!(to_json(&3.14f32), "3.14");
     assert_eq!(to_json(&-0.0), "-0.0");
@@ -0,0 +1,70 @@
+use evil_json::Error;
+use std::io::{self, Write};
+
+/// Accepts `limit` bytes, then fails with `ErrorKind::WriteZero`
+#[derive(Default)]
+struct Limited {
+    data: Vec<u8>,
+    limit: usize,
+    flushed: usize,
+}
+
+impl Limited {
+    fn new(limit: usize) -> Limited {
+        Limited {
+            limit,
+            ..Limited::default()
+        }
+    }
+}
+
+impl Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() + buf.len() > self.limit {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn to_writer() {
+    let mut out = Limited::new(usize::MAX);
+    evil_json::to_writer(&mut out, &[1, 2, 3]).unwrap();
+    assert_eq!(out.data, b"[1,2,3]");
+    assert_eq!(out.flushed, 0);
+}
+
+#[test]
+fn to_writer_flushed() {
+    let mut out = Limited::new(usize::MAX);
+    evil_json::to_writer_flushed(&mut out, &("a", 'b')).unwrap();
+    assert_eq!(out.data, br#"["a","b"]"#);
+    assert_eq!(out.flushed, 1);
+}
+
+#[test]
+fn final_flush_error() {
+    fn check(res: Result<(), Error>) {
+        match res {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    let value = vec!["0123456789"; 4];
+    check(evil_json::to_writer(Limited::new(10), &value));
+    check(evil_json::to_writer_flushed(Limited::new(10), &value));
+    check(evil_json::to_writer_pretty(Limited::new(10), &value));
+    check(evil_json::to_writer_with(
+        Limited::new(10),
+        &value,
+        &Default::default(),
+    ));
+}